@@ -0,0 +1,630 @@
+//! Command line interface of the `supersid` binary.
+
+use std::path::PathBuf;
+use crate::sound_card::config::{Format, SamplingRate, SoundCardConfig};
+use crate::supersid::config::{StationConfig, SuperSidConfig};
+
+pub const DEFAULT_CONFIG: &str = "supersid.toml";
+
+#[derive(Debug, clap::Parser)]
+#[command(name = "supersid", version, about = "Records VLF stations to detect Sudden Ionospheric Disturbances")]
+pub struct Cli {
+    /// Configuration file: .toml, .json or a legacy supersid.cfg
+    #[arg(short, long, global = true, default_value = DEFAULT_CONFIG)]
+    pub config: PathBuf,
+
+    /// Directory files are written to (data_path for `monitor`, current directory otherwise)
+    #[arg(short, long, global = true)]
+    pub output_dir: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub sound_card: SoundCardOverrides,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// Values replacing the ones of the configuration file.
+#[derive(Debug, clap::Args)]
+pub struct SoundCardOverrides {
+    /// ALSA capture device, for instance plughw:CARD=Generic,DEV=0, or file:<path> to read a WAV
+    /// or raw file, or synthetic:<Hz>[@<amplitude>],... to generate tones in noise
+    #[arg(long, global = true)]
+    pub device: Option<String>,
+
+    /// Sampling rate in Hz
+    #[arg(long, global = true)]
+    pub rate: Option<usize>,
+
+    /// Sample format: S16_LE, S24_3LE, S24_LE, S32_LE, FLOAT_LE or U16_LE
+    #[arg(long, global = true)]
+    pub format: Option<String>,
+
+    /// Number of channels to capture or play
+    #[arg(long, global = true)]
+    pub channels: Option<usize>,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum Command {
+    /// Record the configured stations every log_interval seconds
+    Monitor {
+        /// Shell command run at UTC midnight with the day's files as arguments (repeatable)
+        #[arg(long)]
+        post_day: Vec<String>,
+    },
+    /// Record raw samples to a CSV or WAV file
+    Record {
+        /// Duration of the recording
+        #[arg(short, long, default_value_t = 1000)]
+        milliseconds: usize,
+        /// Write a WAV file with the bit depth of the format, which `--device file:<path>` replays
+        #[arg(long)]
+        wav: bool,
+    },
+    /// Record one second, print the station readings and plot the spectrum of each channel
+    Spectrum,
+    /// Sum the power every 100 Hz over a few minutes to find the strongest stations
+    Scan {
+        /// Duration of the scan
+        #[arg(long, default_value_t = 15)]
+        minutes: usize,
+        /// Lowest frequency scanned in Hz
+        #[arg(long, default_value_t = 16000)]
+        from: usize,
+        /// Highest frequency scanned in Hz
+        #[arg(long, default_value_t = 24000)]
+        to: usize,
+    },
+    /// List the ALSA capture devices with the rates, formats and channels they accept
+    Devices,
+    /// Record every rate, format and channel count of the capture devices, check that a test
+    /// tone shows at its frequency and recommend the finest working configuration
+    SelfTest {
+        /// ALSA playback device the test tone is played on, the tested device itself by default
+        #[arg(long)]
+        playback: Option<String>,
+        /// Frequency in Hz of a tone fed by an external generator, nothing is played then
+        #[arg(long)]
+        frequency: Option<f64>,
+        /// Duration of each recording
+        #[arg(short, long, default_value_t = 1000)]
+        milliseconds: usize,
+        /// Highest channel count tried, the maximum of each device by default
+        #[arg(long)]
+        max_channels: Option<usize>,
+    },
+    /// Play tones through a loopback cable into the capture input, measure the frequency
+    /// response of the sound card and save it as a correction curve for the station readings
+    Calibrate {
+        /// ALSA playback device wired to the capture input, the capture device itself by default
+        #[arg(long)]
+        playback: Option<String>,
+        /// Lowest tone in Hz, the reference of the curve
+        #[arg(long, default_value_t = 10000)]
+        from: usize,
+        /// Highest tone in Hz, the ones from half the sampling rate are skipped
+        #[arg(long, default_value_t = 96000)]
+        to: usize,
+        /// Spacing of the tones in Hz
+        #[arg(long, default_value_t = 1000)]
+        step: usize,
+        /// Amplitude of the tones relative to full scale, 0 to 1
+        #[arg(long, default_value_t = 0.5)]
+        amplitude: f64,
+        /// Captured channel measured, 0 being the left one
+        #[arg(long, default_value_t = 0)]
+        channel: usize,
+        /// Calibration file written, calibration_<device>_<rate>.toml by default
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Play a test tone on every channel
+    Tone {
+        /// Tone frequency in Hz
+        #[arg(long, default_value_t = 20000.)]
+        frequency: f64,
+        /// Amplitude relative to full scale, 0 to 1
+        #[arg(long, default_value_t = 0.5)]
+        amplitude: f64,
+        /// Duration of the tone
+        #[arg(long, default_value_t = 10)]
+        seconds: usize,
+    },
+    /// Convert a configuration file between the .cfg, .toml and .json formats
+    Convert {
+        input: PathBuf,
+        /// Output file, .toml or .json
+        output: PathBuf,
+    },
+    /// Create a configuration file interactively
+    Wizard,
+    /// Display the header and first readings of a SID or SuperSID data file
+    Info {
+        file: PathBuf,
+    },
+    /// Write a filtered copy (<name>.filtered.csv) of a raw SID or SuperSID data file
+    Filter {
+        file: PathBuf,
+        /// Half width of the smoothing window, in readings
+        #[arg(short, long, default_value_t = crate::supersid::config::legacy::DEFAULT_BEMA_WING)]
+        bema_wing: usize,
+    },
+}
+
+pub fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    match &cli.command {
+        Command::Monitor { post_day } => monitor(&cli, post_day),
+        Command::Record { milliseconds, wav } => record(&cli, *milliseconds, *wav),
+        Command::Spectrum => spectrum(&cli),
+        Command::Scan { minutes, from, to } => scan(&cli, *minutes, *from, *to),
+        Command::Devices => devices(),
+        Command::SelfTest { playback, frequency, milliseconds, max_channels } => self_test(&cli, playback.as_deref(), *frequency, *milliseconds, *max_channels),
+        Command::Calibrate { playback, from, to, step, amplitude, channel, output } => calibrate(&cli, playback.as_deref(), (*from, *to, *step), *amplitude, *channel, output.as_deref()),
+        Command::Tone { frequency, amplitude, seconds } => tone(&cli, *frequency, *amplitude, *seconds),
+        Command::Convert { input, output } => {
+            SuperSidConfig::load(input)?.save(output)?;
+            println!("Converted '{}' to '{}'.", input.display(), output.display());
+            Ok(())
+        },
+        Command::Wizard => {
+            SuperSidConfig::prompt_new(std::io::stdin().lock(), std::io::stdout())?;
+            Ok(())
+        },
+        Command::Info { file } => info(file),
+        Command::Filter { file, bema_wing } => filter(file, *bema_wing)
+    }
+}
+
+impl Cli {
+    /// Loads the configuration file and applies the command line overrides.
+    pub fn load_config(&self) -> Result<SuperSidConfig, Box<dyn std::error::Error>> {
+        let mut config = SuperSidConfig::load(&self.config)?;
+        let (sound_card, channels) = self.apply_overrides(config.sound_card.clone(), config.channels)?;
+        config.sound_card = sound_card;
+        config.channels = channels;
+        if let Some(output_dir) = &self.output_dir {
+            config.data_path = output_dir.clone();
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Returns the capture settings and station list, falling back to the legacy defaults when
+    /// there is no configuration file, so that a bare device can be tried out.
+    fn sound_card_settings(&self) -> Result<(SoundCardConfig, usize, Vec<StationConfig>), Box<dyn std::error::Error>> {
+        if self.config.exists() {
+            let config = self.load_config()?;
+            return Ok((config.sound_card, config.channels, config.stations));
+        }
+
+        let defaults = SoundCardConfig::new(
+            crate::supersid::config::legacy::DEFAULT_DEVICE,
+            Format::B16,
+            SamplingRate::Hz48000,
+            crate::supersid::config::legacy::DEFAULT_PERIOD_SIZE
+        );
+        let (sound_card, channels) = self.apply_overrides(defaults, 1)?;
+        Ok((sound_card, channels, Vec::new()))
+    }
+
+    fn apply_overrides(&self, mut sound_card: SoundCardConfig, mut channels: usize) -> Result<(SoundCardConfig, usize), Box<dyn std::error::Error>> {
+        if let Some(device) = &self.sound_card.device {
+            sound_card.device_id = device.clone();
+        }
+        if let Some(rate) = self.sound_card.rate {
+            sound_card.sampling_rate = match SamplingRate::from_value(rate) {
+                Some(sampling_rate) => sampling_rate,
+                None => return Err(format!("unsupported sampling rate {} Hz, use a rate within {} and {} Hz", rate, SamplingRate::MIN_CUSTOM, SamplingRate::MAX_CUSTOM).into())
+            };
+        }
+        if let Some(format) = &self.sound_card.format {
+            sound_card.format = match Format::from_label(format) {
+                Some(format) => format,
+                None => return Err(format!("unsupported format '{}', use S16_LE, S24_3LE, S24_LE, S32_LE, FLOAT_LE or U16_LE", format).into())
+            };
+        }
+        if let Some(override_channels) = self.sound_card.channels {
+            channels = override_channels;
+        }
+        Ok((sound_card, channels))
+    }
+
+    fn output_path(&self, file_name: &str) -> Result<PathBuf, std::io::Error> {
+        match &self.output_dir {
+            Some(output_dir) => {
+                std::fs::create_dir_all(output_dir)?;
+                Ok(output_dir.join(file_name))
+            },
+            None => Ok(PathBuf::from(file_name))
+        }
+    }
+}
+
+fn spectra(config: &SoundCardConfig, data: &[crate::sound_card::ChannelData<f64>]) -> Vec<crate::spectral_density::SpectralDensity<f64>> {
+    let sampling_rate = config.sampling_rate.sample_value::<f64>();
+    let n = crate::supersid::get_N(sampling_rate);
+    data.iter().map(|channel| crate::spectral_density::SpectralDensity::<f64>::new(&channel.channel_data, sampling_rate, n).with_record_times(channel.record_start, channel.record_end)).collect()
+}
+
+/// Set by the SIGINT and SIGTERM handler to end `monitor` cleanly.
+static STOP: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+extern "C" fn request_stop(_signal: libc::c_int) {
+    STOP.store(true, std::sync::atomic::Ordering::Relaxed);
+}
+
+fn monitor(cli: &Cli, post_day: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let config = cli.load_config()?;
+    let handler = request_stop as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+
+    match config.sound_card.format {
+        Format::B16 => monitor_as::<i16>(&config, post_day),
+        Format::B24 => monitor_as::<crate::math::i24>(&config, post_day),
+        Format::B32 => monitor_as::<i32>(&config, post_day),
+        Format::B24In32 => monitor_as::<crate::math::i24in32>(&config, post_day),
+        Format::F32 => monitor_as::<f32>(&config, post_day),
+        Format::U16 => monitor_as::<u16>(&config, post_day)
+    }
+}
+
+fn monitor_as<T: crate::math::Sample + ::alsa::pcm::IoFormat + 'static>(config: &SuperSidConfig, post_day: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    use crate::supersid::logger::{CommandHook, ConsoleLogger, SidFileLogger, SuperSidLogger};
+
+    let (recorder, description) = crate::sound_card::create_recorder::<T>(&config.sound_card, config.channels, crate::sound_card::Pacing::RealTime)?;
+    eprintln!("Capturing from '{}' {}.", config.sound_card.device_id, description);
+    let mut supersid = crate::supersid::SuperSid::<T, f64>::new(config, recorder);
+    if let Some(calibration) = crate::supersid::calibration::Calibration::for_config(config)? {
+        eprintln!("Correcting the readings by the calibration of '{}': {}.", calibration.device_id, calibration);
+        supersid = supersid.with_calibration(calibration);
+    }
+    let mut sid_file_logger = SidFileLogger::new(config, chrono::Utc::now().date_naive());
+    for path in sid_file_logger.resume()? {
+        eprintln!("Continue recording with data from '{}' included.", path.display());
+    }
+    for command in post_day.iter() {
+        sid_file_logger.add_hook(Box::new(CommandHook::new(command)));
+    }
+    let mut loggers: Vec<Box<dyn SuperSidLogger<f64>>> = vec![Box::new(ConsoleLogger::new(config.log_interval)), Box::new(sid_file_logger)];
+
+    eprintln!("Monitoring {} station(s) every {} s, press Ctrl+C to stop.", config.stations.len(), config.log_interval);
+    supersid.run(&mut loggers, &STOP)?;
+    Ok(())
+}
+
+fn record(cli: &Cli, milliseconds: usize, wav: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let (sound_card, channels, _) = cli.sound_card_settings()?;
+    let data = crate::sound_card::record_f64(&sound_card, channels, milliseconds, crate::sound_card::Pacing::AsFastAsPossible)?;
+    let length = data.iter().map(|channel| channel.channel_data.len()).min().unwrap_or(0);
+
+    let seconds = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs();
+    let path = cli.output_path(&format!("record_{}.{}", seconds, if wav { "wav" } else { "csv" }))?;
+    if wav {
+        // Back from the common amplitude scale to the samples of the format.
+        let samples: Vec<crate::sound_card::ChannelData<f64>> = data.iter()
+            .map(|channel| crate::sound_card::ChannelData::<f64> {
+                channel_data: channel.channel_data.iter().map(|sample| sound_card.format.denormalize(*sample)).collect(),
+                ..channel.clone()
+            })
+            .collect();
+        crate::sound_card::wav::save(&path, &samples, sound_card.format, sound_card.sampling_rate.value())?;
+    }
+    else {
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(&path)?);
+
+        use std::io::Write;
+        let header: Vec<String> = data.iter().map(|channel| format!("channel_{}", channel.channel_num)).collect();
+        writeln!(writer, "{}", header.join(","))?;
+        let mut i = 0usize;
+        while i < length {
+            let row: Vec<String> = data.iter().map(|channel| channel.channel_data[i].to_string()).collect();
+            writeln!(writer, "{}", row.join(","))?;
+            i += 1;
+        }
+        writer.flush()?;
+    }
+
+    println!("Recorded {} frames of {} channel(s) to '{}'.", length, data.len(), path.display());
+    if let Some(channel) = data.first() {
+        if let (Some(start), Some(end)) = (channel.record_start, channel.record_end) {
+            println!("First frame at {}, last frame at {} UTC.", start.format(crate::supersid::sidfile::TIMESTAMP_EXTENDED), end.format(crate::supersid::sidfile::TIMESTAMP_EXTENDED));
+        }
+    }
+    Ok(())
+}
+
+fn spectrum(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let (sound_card, channels, stations) = cli.sound_card_settings()?;
+    let data = crate::sound_card::record_f64(&sound_card, channels, 1000, crate::sound_card::Pacing::AsFastAsPossible)?;
+    let spec_density = spectra(&sound_card, &data);
+
+    println!("------------------------------------------");
+    for (i, sd) in spec_density.iter().enumerate() {
+        println!("Channel {}: {} bins of {} Hz, noise floor {} dB/Hz", i + 1, sd.data.len(), sd.freq_step, 10. * sd.noise_floor.log10());
+        if let Some(peak) = sd.peak {
+            println!("  Peak at {} Hz: {} dB/Hz", peak.frequency(), peak.spectral_density_db());
+        }
+        for station in stations.iter() {
+            if let Some(sample) = sd.data.iter().nth(station.get_bin(sd.freq_step)) {
+                println!("  Station {} ({} Hz): measured frequency {} Hz; measured power: {} dB/Hz", station.callsign, station.frequency, sample.frequency(), sample.spectral_density_db());
+            }
+        }
+
+        let path = cli.output_path(&format!("spectral_density_channel_{}.png", i + 1))?;
+        crate::spectral_density::plotter::plot_spectrum::<f64>(sd, path.to_string_lossy().to_string(), None, Some("Frequency [Hz]"), Some("Spectral density [s^2/Hz]"));
+        println!("  Plot written to '{}'.", path.display());
+    }
+    for station in stations.iter() {
+        if let Some(sample) = station.read(&spec_density) {
+            println!("Station {} read from channel {}: {} dB/Hz", station.callsign, station.channel.label(), sample.spectral_density_db());
+        }
+    }
+    println!("------------------------------------------");
+    Ok(())
+}
+
+fn scan(cli: &Cli, minutes: usize, from: usize, to: usize) -> Result<(), Box<dyn std::error::Error>> {
+    const SCAN_STEP: usize = 100;
+    const SCAN_INTERVAL_SECONDS: u64 = 5;
+    const TOP_FREQUENCIES: usize = 20;
+
+    let (sound_card, channels, _) = cli.sound_card_settings()?;
+    let nyquist = sound_card.sampling_rate.value() / 2;
+    if from > to || to > nyquist {
+        return Err(format!("the scanned range must be within 0 and {} Hz", nyquist).into());
+    }
+
+    let frequencies: Vec<StationConfig> = (from..=to).step_by(SCAN_STEP).map(|frequency| StationConfig::new(&format!("ST_{}", frequency), 'k', frequency)).collect();
+    let mut totals = vec![0f64; frequencies.len() * channels];
+
+    println!("Scanning for {} minutes on [{}:{}]...", minutes, from, to);
+    let end = std::time::Instant::now() + std::time::Duration::from_secs(minutes as u64 * 60);
+    let mut readings = 0usize;
+    while std::time::Instant::now() < end {
+        let data = crate::sound_card::record_f64(&sound_card, channels, 1000, crate::sound_card::Pacing::AsFastAsPossible)?;
+        for (channel, sd) in spectra(&sound_card, &data).iter().enumerate() {
+            let bins: Vec<f64> = sd.data.iter().map(|sample| sample.spectral_density()).collect();
+            for (i, frequency) in frequencies.iter().enumerate() {
+                if let Some(power) = bins.get(frequency.get_bin(sd.freq_step)) {
+                    totals[channel * frequencies.len() + i] += power;
+                }
+            }
+        }
+        readings += 1;
+        std::thread::sleep(std::time::Duration::from_secs(SCAN_INTERVAL_SECONDS));
+    }
+
+    if readings == 0 {
+        return Err("no reading was taken".into());
+    }
+
+    let mut ranking: Vec<(usize, usize, f64)> = totals.iter().enumerate()
+        .map(|(i, total)| (i / frequencies.len(), frequencies[i % frequencies.len()].frequency, total / readings as f64))
+        .collect();
+    ranking.sort_by(|(_, _, a), (_, _, b)| b.total_cmp(a));
+
+    println!("Strongest frequencies over {} readings:", readings);
+    for (channel, frequency, power) in ranking.iter().take(TOP_FREQUENCIES) {
+        let known: Vec<&str> = crate::supersid::config::transmitters::VLF_TRANSMITTERS.iter()
+            .filter(|transmitter| transmitter.frequency.abs_diff(*frequency) < SCAN_STEP)
+            .map(|transmitter| transmitter.callsign)
+            .collect();
+        println!("  channel {} {:6} Hz {:10.2} dB/Hz  {}", channel + 1, frequency, 10. * power.log10(), known.join(" "));
+    }
+    Ok(())
+}
+
+fn devices() -> Result<(), Box<dyn std::error::Error>> {
+    let devices = crate::sound_card::alsa::discover_capture_devices()?;
+    if devices.is_empty() {
+        println!("No ALSA capture device found.");
+    }
+    for device in devices.iter() {
+        println!("{}\n    {}", device.name, device.description);
+        match &device.capabilities {
+            Ok(capabilities) => println!("    {}", capabilities),
+            Err(error) => println!("    {}", error)
+        }
+    }
+    Ok(())
+}
+
+/// Tries what `find_alsa_devices.py` tries: each rate, format and channel count a device accepts,
+/// recorded while a tone of a third of the rate plays, unless an external generator is given.
+fn self_test(cli: &Cli, playback: Option<&str>, frequency: Option<f64>, milliseconds: usize, max_channels: Option<usize>) -> Result<(), Box<dyn std::error::Error>> {
+    let (base, configured_channels, _) = cli.sound_card_settings()?;
+
+    // The configured device only when given on the command line, else every capture device.
+    let devices: Vec<(String, Result<crate::sound_card::alsa::CaptureCapabilities, crate::sound_card::SoundCardError>)> = match &cli.sound_card.device {
+        Some(device) if crate::sound_card::is_alsa_device(device) => vec![(device.clone(), crate::sound_card::alsa::probe_capture(device))],
+        Some(device) => vec![(device.clone(), Err(crate::sound_card::SoundCardError::Driver { device: device.clone(), operation: "probe the device".to_string(), message: "not an ALSA device".to_string() }))],
+        None => crate::sound_card::alsa::discover_capture_devices()?.into_iter().map(|device| (device.name, device.capabilities)).collect()
+    };
+    if devices.is_empty() {
+        println!("No ALSA capture device found.");
+        return Ok(());
+    }
+
+    println!("{:<40} {:>7} {:<8} {:>8} {:>8}  {:<24} Result", "Device", "Rate", "Format", "Channels", "Duration", "Peak frequency");
+    let mut results = Vec::<crate::sound_card::self_test::SelfTestResult>::new();
+    for (device, capabilities) in devices.iter() {
+        // Files and synthetic tones are tested with the configured settings only.
+        let combinations: Vec<(SamplingRate, Format, usize)> = match capabilities {
+            Ok(capabilities) => {
+                let channel_counts: Vec<usize> = match cli.sound_card.channels {
+                    Some(channels) => vec![channels],
+                    None => (capabilities.channels.0..=max_channels.map_or(capabilities.channels.1, |max| std::cmp::min(max, capabilities.channels.1))).collect()
+                };
+                channel_counts.iter()
+                    .flat_map(|channels| capabilities.combinations(*channels).into_iter().map(move |(sampling_rate, format)| (sampling_rate, format, *channels)))
+                    .filter(|(sampling_rate, _, _)| cli.sound_card.rate.is_none() || *sampling_rate == base.sampling_rate)
+                    .filter(|(_, format, _)| cli.sound_card.format.is_none() || *format == base.format)
+                    .collect()
+            },
+            Err(_) if !crate::sound_card::is_alsa_device(device) => vec![(base.sampling_rate, base.format, configured_channels)],
+            Err(error) => {
+                println!("{:<40} {}", device, error);
+                continue;
+            }
+        };
+
+        for (sampling_rate, format, channels) in combinations {
+            let mut config = base.clone();
+            config.device_id = device.clone();
+            config.sampling_rate = sampling_rate;
+            config.format = format;
+
+            let tone_device = match (frequency, playback) {
+                (Some(_), _) => None,
+                (None, Some(playback)) => Some(playback.to_string()),
+                (None, None) if crate::sound_card::is_alsa_device(device) => Some(device.clone()),
+                (None, None) => None
+            };
+            let result = crate::sound_card::self_test::test_configuration(config, channels, milliseconds, tone_device, frequency);
+            let peaks: Vec<String> = result.peaks.iter().map(|peak| format!("{:.0}", peak)).collect();
+            let outcome = match (&result.error, result.tone) {
+                (Some(error), _) => error.clone(),
+                (None, Some(tone)) => format!("OK, tone at {} Hz", tone),
+                (None, None) => "OK, no tone to check".to_string()
+            };
+            println!("{:<40} {:>7} {:<8} {:>8} {:>6} ms  {:<24} {}", device, sampling_rate.value(), format.label(), channels, result.elapsed.as_millis(), peaks.join(" "), outcome);
+            results.push(result);
+        }
+    }
+
+    let best = match crate::sound_card::self_test::recommend(&results) {
+        Some(best) => best,
+        None => {
+            println!("No working configuration found.");
+            return Ok(());
+        }
+    };
+
+    #[derive(serde::Serialize)]
+    struct Recommended<'a> {
+        channels: usize,
+        sound_card: &'a SoundCardConfig
+    }
+    let snippet = toml::to_string_pretty(&Recommended { channels: best.channels, sound_card: &best.config })?;
+    println!("\n{} of {} configurations work. Recommended settings for supersid.toml:\n\n{}", results.iter().filter(|result| result.is_ok()).count(), results.len(), snippet);
+    Ok(())
+}
+
+fn tone(cli: &Cli, frequency: f64, amplitude: f64, seconds: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let (sound_card, channels, _) = cli.sound_card_settings()?;
+    if !(0f64..=1f64).contains(&amplitude) {
+        return Err("the amplitude must be within 0 and 1".into());
+    }
+    if frequency <= 0. || frequency > sound_card.sampling_rate.sample_value::<f64>() / 2. {
+        return Err(format!("the frequency must be within 0 and {} Hz", sound_card.sampling_rate.value() / 2).into());
+    }
+
+    println!("Playing {} Hz on '{}' for {} seconds...", frequency, sound_card.device_id, seconds);
+    match sound_card.format {
+        Format::B16 => crate::sound_card::play_tone::<i16>(&sound_card, channels, frequency, amplitude, seconds)?,
+        Format::B24 => crate::sound_card::play_tone::<crate::math::i24>(&sound_card, channels, frequency, amplitude, seconds)?,
+        Format::B32 => crate::sound_card::play_tone::<i32>(&sound_card, channels, frequency, amplitude, seconds)?,
+        Format::B24In32 => crate::sound_card::play_tone::<crate::math::i24in32>(&sound_card, channels, frequency, amplitude, seconds)?,
+        Format::F32 => crate::sound_card::play_tone::<f32>(&sound_card, channels, frequency, amplitude, seconds)?,
+        Format::U16 => crate::sound_card::play_tone::<u16>(&sound_card, channels, frequency, amplitude, seconds)?
+    };
+    Ok(())
+}
+
+/// Plays tones through a playback PCM linked to the capture one, which a loopback cable wires
+/// to the capture input, and saves the frequency response as the correction curve of the device.
+fn calibrate(cli: &Cli, playback: Option<&str>, range: (usize, usize, usize), amplitude: f64, channel: usize, output: Option<&std::path::Path>) -> Result<(), Box<dyn std::error::Error>> {
+    let (sound_card, channels, _) = cli.sound_card_settings()?;
+    let (from, to, step) = range;
+    if !crate::sound_card::is_alsa_device(&sound_card.device_id) {
+        return Err("the calibration needs an ALSA capture device".into());
+    }
+    if !(0f64..=1f64).contains(&amplitude) || amplitude == 0. {
+        return Err("the amplitude must be within 0 and 1".into());
+    }
+    if channel >= channels {
+        return Err(format!("the channel must be lower than the {} captured channel(s)", channels).into());
+    }
+    let nyquist = sound_card.sampling_rate.value() / 2;
+    let frequencies: Vec<usize> = (from..=to).step_by(std::cmp::max(step, 1)).filter(|frequency| *frequency > 0 && *frequency < nyquist).collect();
+    if frequencies.is_empty() {
+        return Err(format!("no tone within {} and {} Hz is below the Nyquist frequency of {} Hz", from, to, nyquist).into());
+    }
+
+    let playback = playback.unwrap_or(&sound_card.device_id);
+    println!("Playing {} tones from {} to {} Hz on '{}' into '{}'...", frequencies.len(), frequencies[0], frequencies[frequencies.len() - 1], playback, sound_card.device_id);
+    let played_power = crate::supersid::calibration::played_power(amplitude);
+    let measurements = crate::supersid::calibration::measure(&sound_card, playback, channels, channel, &frequencies, amplitude, &mut |frequency, power| match power {
+        Some(power) => println!("  {:6} Hz {:8.2} dB", frequency, 10. * (power / played_power).log10()),
+        None => println!("  {:6} Hz below the noise floor", frequency)
+    })?;
+
+    let calibration = match crate::supersid::calibration::Calibration::from_measurements(&sound_card.device_id, sound_card.sampling_rate.value(), played_power, &measurements) {
+        Some(calibration) => calibration,
+        None => return Err("no tone came through, check that the playback output is wired to the capture input".into())
+    };
+
+    let path = match output {
+        Some(output) => output.to_path_buf(),
+        None => {
+            let device: String = sound_card.device_id.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+            cli.output_path(&format!("calibration_{}_{}.toml", device, sound_card.sampling_rate.value()))?
+        }
+    };
+    calibration.save(&path)?;
+    println!("Calibration written to '{}': {}, loopback gain {:.1} dB.", path.display(), calibration, calibration.loopback_gain_db);
+    println!("Set calibration = \"{}\" in the configuration to correct the station readings.", path.display());
+    Ok(())
+}
+
+fn info(file: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+    const SHOWN_READINGS: usize = 5;
+
+    let sid_file = crate::supersid::sidfile::SidFile::from_file(file)?;
+    let header = &sid_file.header;
+    println!("----- Header information -----");
+    println!("Format: {}", sid_file.log_format().label());
+    println!("Site: {} ({}, {}), monitor {}", header.site, header.latitude, header.longitude, header.monitor_id);
+    println!("Stations: {}", header.stations.iter().zip(header.frequencies.iter()).map(|(station, frequency)| format!("{} ({} Hz)", station, frequency)).collect::<Vec<String>>().join(", "));
+    println!("Start time: {}, every {} s, {}", header.utc_start_time, header.log_interval, header.log_type.label());
+    if let Some(sampling_rate) = header.sampling_rate {
+        println!("Sampling rate: {} Hz", sampling_rate);
+    }
+    println!("Number of timestamps: {}", sid_file.timestamps.len());
+
+    // Show the first readings that are not zero, as sidfile.py --info does.
+    let first = sid_file.data.first().and_then(|series| series.iter().position(|value| *value != 0f64)).unwrap_or(0);
+    println!("----- Readings from #{} -----", first);
+    let mut i = first;
+    while i < sid_file.timestamps.len() && i < first + SHOWN_READINGS {
+        let row: Vec<String> = sid_file.data.iter().map(|series| crate::supersid::sidfile::format_value(series[i])).collect();
+        println!("{}, {}", sid_file.timestamps[i].format(crate::supersid::sidfile::TIMESTAMP_EXTENDED), row.join(", "));
+        i += 1;
+    }
+    Ok(())
+}
+
+fn filter(file: &std::path::Path, bema_wing: usize) -> Result<(), Box<dyn std::error::Error>> {
+    use crate::supersid::config::LogType;
+
+    let sid_file = crate::supersid::sidfile::SidFile::from_file(file)?;
+    if sid_file.header.log_type != LogType::Raw {
+        eprintln!("Warning: '{}' is not a raw file, its data is filtered again.", file.display());
+    }
+
+    let stem = file.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    let path = file.with_file_name(format!("{}.filtered.csv", stem));
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(&path)?);
+    if sid_file.is_supersid {
+        sid_file.write_supersid(&mut writer, LogType::Filtered, sid_file.is_extended, bema_wing)?;
+    } else {
+        sid_file.write_sid(&mut writer, 0, LogType::Filtered, sid_file.is_extended, bema_wing)?;
+    }
+    std::io::Write::flush(&mut writer)?;
+    println!("Filtered data written to '{}'.", path.display());
+    Ok(())
+}
@@ -18,6 +18,7 @@ impl Sample for u32 {}
 impl Sample for i32 {}
 impl Sample for crate::math::u24 {}
 impl Sample for crate::math::i24 {}
+impl Sample for crate::math::i24in32 {}
 
 
 
@@ -42,6 +43,10 @@ pub fn generate_tone_with_noise<T: Sample + ::num_traits::ToPrimitive>(freq: f64
         }).collect()
 }
 
+/// Range of the 24-bit samples.
+pub const I24_MIN: i32 = -(1 << 23);
+pub const I24_MAX: i32 = (1 << 23) - 1;
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct i24 {
@@ -61,43 +66,22 @@ impl i24 {
         Self { first_byte: first_byte, second_byte: second_byte, third_byte: third_byte }
     }
 
+    /// Returns the value, sign extended from bit 23. The bytes are in the native order, as ALSA
+    /// hands them over for `S24_3LE` on little endian hosts and `S24_3BE` on big endian ones.
     pub fn value_to_i32(&self) -> i32 {
-
-        let mut val: i32 = 0;
-        // Add the 8 ncessary bits for negative integers.
-        let mut neg = cfg!(target_endian = "big") && (self.third_byte & 0x01) == 0x01;
-        neg = neg || self.first_byte >= 0x80;
-        if (neg) {
-            val |= 0xFF as i32;
-            val <<= 8;
-        }
-        val |= self.first_byte as i32;
-        val <<= 8;
-        val |= self.second_byte as i32;
-        val <<= 8;
-        val |= self.third_byte as i32;
         if cfg!(target_endian = "big") {
-            val <<= 8;
+            i32::from_be_bytes([self.first_byte, self.second_byte, self.third_byte, 0]) >> 8
+        }
+        else {
+            i32::from_le_bytes([self.first_byte, self.second_byte, self.third_byte, 0]) << 8 >> 8
         }
-        return val;
     }
 
+    /// Clips `val` to the 24-bit range.
     pub fn value_from_i32(val: i32) -> Self {
-        let mut new_val: i32;
-        let mut new_u24 = Self {first_byte: 0xFF, second_byte: 0xFF, third_byte: 0xFF };
-        // Chop the 8 unncessary bits.
-        if cfg!(target_endian = "big") {
-            new_val = val >> 8;
-        }
-        else {
-            new_val = (val << 8) >> 8;
-        }
-        new_u24.third_byte &= new_val as u8;
-        new_val >>= 8;
-        new_u24.second_byte &= new_val as u8;
-        new_val >>= 8;
-        new_u24.first_byte &= new_val as u8;
-        return new_u24;
+        let val = val.clamp(I24_MIN, I24_MAX);
+        let bytes = if cfg!(target_endian = "big") { (val << 8).to_be_bytes() } else { val.to_le_bytes() };
+        Self { first_byte: bytes[0], second_byte: bytes[1], third_byte: bytes[2] }
     }
 }
 
@@ -125,34 +109,20 @@ impl u24 {
         Self { first_byte: first_byte, second_byte: second_byte, third_byte: third_byte }
     }
 
+    /// Returns the value, with the bytes in the native order as for [`i24::value_to_i32`].
     pub fn value_to_u32(&self) -> u32 {
-        let mut val: u32 = 0;
-        val |= self.first_byte as u32;
-        val <<= 8;
-        val |= self.second_byte as u32;
-        val <<= 8;
-        val |= self.third_byte as u32;
         if cfg!(target_endian = "big") {
-            val <<= 8;
+            u32::from_be_bytes([self.first_byte, self.second_byte, self.third_byte, 0]) >> 8
+        }
+        else {
+            u32::from_le_bytes([self.first_byte, self.second_byte, self.third_byte, 0])
         }
-        return val;
     }
 
+    /// Keeps the 24 low bits of `val`.
     pub fn value_from_u32(val: u32) -> Self {
-        let mut new_val: u32;
-        let mut new_u24 = Self {first_byte: 0xFF, second_byte: 0xFF, third_byte: 0xFF };
-        if cfg!(target_endian = "big") {
-            new_val = val >> 8;
-        }
-        else {
-            new_val = (val << 8) >> 8;
-        }
-        new_u24.third_byte &= new_val as u8;
-        new_val >>= 8;
-        new_u24.second_byte &= new_val as u8;
-        new_val >>= 8;
-        new_u24.first_byte &= new_val as u8;
-        return new_u24;
+        let bytes = if cfg!(target_endian = "big") { (val << 8).to_be_bytes() } else { val.to_le_bytes() };
+        Self { first_byte: bytes[0], second_byte: bytes[1], third_byte: bytes[2] }
     }
 }
 
@@ -161,6 +131,24 @@ impl Default for u24 {
     fn default() -> Self { Self { first_byte: 0, second_byte: 0, third_byte: 0 } }
 }
 
+/// A 24-bit sample in the low bytes of 32 bits, as ALSA `S24_LE`. The high byte is padding
+/// some drivers leave undefined, so it is ignored and the value sign extended from bit 23.
+#[allow(non_camel_case_types)]
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct i24in32(pub i32);
+
+impl i24in32 {
+    pub fn value_to_i32(&self) -> i32 {
+        self.0 << 8 >> 8
+    }
+
+    /// Clips `val` to the 24-bit range.
+    pub fn value_from_i32(val: i32) -> Self {
+        Self(val.clamp(I24_MIN, I24_MAX))
+    }
+}
+
 
 macro_rules! impl_i24_from_small {
     ($Small: ty) => {
@@ -319,6 +307,7 @@ macro_rules! impl_from_primitive {
 
 impl_from_primitive!(i24, to_i32, value_from_i32);
 impl_from_primitive!(u24, to_u32, value_from_u32);
+impl_from_primitive!(i24in32, to_i32, value_from_i32);
 
 
 macro_rules! impl_to_primitive {
@@ -389,6 +378,7 @@ macro_rules! impl_to_primitive {
 
 impl_to_primitive!(i24, from_i32, value_to_i32);
 impl_to_primitive!(u24, from_u32, value_to_u32);
+impl_to_primitive!(i24in32, from_i32, value_to_i32);
 
 
 // pub struct SigFigInt<T: Num, U: PrimInt> {
@@ -411,3 +401,37 @@ impl_to_primitive!(u24, from_u32, value_to_u32);
 
 // }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_traits::FromPrimitive;
+
+    #[test]
+    fn i24_sign_extends_from_bit_23() {
+        assert_eq!(i24::new_bytes(0xff, 0xff, 0xff).value_to_i32(), -1);
+        assert_eq!(i24::new_bytes(0x00, 0x00, 0x80).value_to_i32(), I24_MIN);
+        assert_eq!(i24::new_bytes(0xff, 0xff, 0x7f).value_to_i32(), I24_MAX);
+        // The padding byte is ignored.
+        assert_eq!(i24in32(0x12ff_ffff).value_to_i32(), -1);
+        assert_eq!(i24in32(0x7f80_0000).value_to_i32(), I24_MIN);
+    }
+
+    #[test]
+    fn i24_round_trips() {
+        for value in [I24_MIN, -70000, -1, 0, 1, 70000, I24_MAX] {
+            assert_eq!(i24::value_from_i32(value).value_to_i32(), value);
+            assert_eq!(i24in32::value_from_i32(value).value_to_i32(), value);
+        }
+    }
+
+    #[test]
+    fn i24_clips_at_full_scale() {
+        assert_eq!(i24::value_from_i32(I24_MAX + 1).value_to_i32(), I24_MAX);
+        assert_eq!(i24::value_from_i32(i32::MIN).value_to_i32(), I24_MIN);
+        assert_eq!(i24in32::value_from_i32(I24_MAX + 1).value_to_i32(), I24_MAX);
+        assert_eq!(i24in32::value_from_i32(I24_MIN - 1).value_to_i32(), I24_MIN);
+        assert_eq!(i24::from_f64(1e7).unwrap().value_to_i32(), I24_MAX);
+        assert_eq!(i24in32::from_f64(-1e7).unwrap().value_to_i32(), I24_MIN);
+    }
+}
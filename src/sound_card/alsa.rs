@@ -1,7 +1,7 @@
 use num_traits::ToPrimitive;
 
 pub trait AlsaSoundCardLink {
-    fn link<'a, T: AlsaSoundCardLink>(&'a mut self, other: &'a mut T) -> Result<(), std::io::Error>;
+    fn link<'a, T: AlsaSoundCardLink>(&'a mut self, other: &'a mut T) -> Result<(), super::SoundCardError>;
     fn get_pcm<'a>(&'a mut self) -> &'a ::alsa::pcm::PCM;
 }
 
@@ -19,6 +19,167 @@ impl alsa::pcm::IoFormat for crate::math::i24 {
     const FORMAT: alsa::pcm::Format = alsa::pcm::Format::S243BE;
 }
 
+impl alsa::pcm::IoFormat for crate::math::i24in32 {
+    #[cfg(target_endian = "little")]
+    const FORMAT: alsa::pcm::Format = alsa::pcm::Format::S24LE;
+    #[cfg(target_endian = "big")]
+    const FORMAT: alsa::pcm::Format = alsa::pcm::Format::S24BE;
+}
+
+/// Name and description of an ALSA PCM able to capture.
+#[derive(Debug, Clone)]
+pub struct AlsaDeviceName {
+    pub name: String,
+    pub description: String
+}
+
+/// Lists the PCM names ALSA advertises for capture.
+pub fn capture_device_names() -> Result<Vec<AlsaDeviceName>, super::SoundCardError> {
+    let hints = match ::alsa::device_name::HintIter::new_str(None, "pcm") {
+        Ok(hint_iter) => hint_iter,
+        Err(error) => return Err(super::SoundCardError::from_alsa("pcm", "list devices", error))
+    };
+
+    Ok(hints
+        .filter(|hint| hint.direction.is_none() || hint.direction == Some(::alsa::Direction::Capture))
+        .filter_map(|hint| match hint.name {
+            Some(name) if name != "null" => Some(AlsaDeviceName {
+                name,
+                description: hint.desc.unwrap_or_default().replace('\n', " ")
+            }),
+            _ => None
+        })
+        .collect())
+}
+
+/// Hardware parameter ranges of a capture PCM.
+#[derive(Debug, Clone)]
+pub struct CaptureCapabilities {
+    /// Lowest and highest rate in Hz.
+    pub rate_range: (usize, usize),
+    /// Rates of [`super::config::SamplingRate::COMMON`] the device accepts.
+    pub sampling_rates: Vec<super::config::SamplingRate>,
+    pub formats: Vec<super::config::Format>,
+    pub channels: (usize, usize),
+    /// Lowest and highest period size in frames.
+    pub period_size: (usize, usize),
+    /// Lowest and highest buffer size in frames.
+    pub buffer_size: (usize, usize)
+}
+
+impl CaptureCapabilities {
+    /// Returns the rate and format pairs usable with `channels`, the finest first.
+    pub fn combinations(&self, channels: usize) -> Vec<(super::config::SamplingRate, super::config::Format)> {
+        if channels < self.channels.0 || channels > self.channels.1 {
+            return Vec::new();
+        }
+        self.sampling_rates.iter()
+            .flat_map(|sampling_rate| self.formats.iter().map(move |format| (*sampling_rate, *format)))
+            .collect()
+    }
+}
+
+impl std::fmt::Display for CaptureCapabilities {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rates: Vec<String> = self.sampling_rates.iter().map(|sampling_rate| sampling_rate.value().to_string()).collect();
+        let formats: Vec<&str> = self.formats.iter().map(|format| format.label()).collect();
+        write!(f, "rates {}-{} Hz ({}), formats {}, channels {}-{}, period {}-{} frames, buffer {}-{} frames",
+            self.rate_range.0, self.rate_range.1, rates.join(", "), formats.join(" "),
+            self.channels.0, self.channels.1, self.period_size.0, self.period_size.1, self.buffer_size.0, self.buffer_size.1)
+    }
+}
+
+/// A capture PCM found by [`discover_capture_devices`].
+#[derive(Debug)]
+pub struct CaptureDevice {
+    pub name: String,
+    pub description: String,
+    /// What the device accepts, or why it could not be probed, for instance while busy.
+    pub capabilities: Result<CaptureCapabilities, super::SoundCardError>
+}
+
+/// Reads the hardware parameter ranges of `device_id` for capture, without recording.
+pub fn probe_capture(device_id: &str) -> Result<CaptureCapabilities, super::SoundCardError> {
+    // Non blocking, so a device held by another program fails instead of waiting.
+    let pcm = match ::alsa::pcm::PCM::new(device_id, ::alsa::Direction::Capture, true) {
+        Ok(pcm) => pcm,
+        Err(error) => return Err(super::SoundCardError::from_alsa(device_id, "open the PCM", error))
+    };
+    let hwp = match ::alsa::pcm::HwParams::any(&pcm) {
+        Ok(hwp) => hwp,
+        Err(error) => return Err(super::SoundCardError::from_alsa(device_id, "read the hardware parameters", error))
+    };
+    let range = |min: Result<u32, ::alsa::Error>, max: Result<u32, ::alsa::Error>| match (min, max) {
+        (Ok(min), Ok(max)) => Ok((min as usize, max as usize)),
+        (Err(error), _) | (_, Err(error)) => Err(super::SoundCardError::from_alsa(device_id, "read the hardware parameters", error))
+    };
+    let frames_range = |min: Result<::alsa::pcm::Frames, ::alsa::Error>, max: Result<::alsa::pcm::Frames, ::alsa::Error>| match (min, max) {
+        (Ok(min), Ok(max)) => Ok((min as usize, max as usize)),
+        (Err(error), _) | (_, Err(error)) => Err(super::SoundCardError::from_alsa(device_id, "read the hardware parameters", error))
+    };
+
+    Ok(CaptureCapabilities {
+        rate_range: range(hwp.get_rate_min(), hwp.get_rate_max())?,
+        sampling_rates: super::config::SamplingRate::COMMON.iter()
+            .filter(|sampling_rate| hwp.test_rate(sampling_rate.value() as u32).is_ok())
+            .copied()
+            .collect(),
+        formats: super::config::Format::ALL.iter()
+            .filter(|format| hwp.test_format(AlsaSoundCard::<i32>::get_format(**format)).is_ok())
+            .copied()
+            .collect(),
+        channels: range(hwp.get_channels_min(), hwp.get_channels_max())?,
+        period_size: frames_range(hwp.get_period_size_min(), hwp.get_period_size_max())?,
+        buffer_size: frames_range(hwp.get_buffer_size_min(), hwp.get_buffer_size_max())?
+    })
+}
+
+/// Returns the `hw` and `plughw` PCMs of every sound card with a capture device, as
+/// `(name, description)`.
+fn card_capture_pcms() -> Vec<(String, String)> {
+    let mut pcms = Vec::<(String, String)>::new();
+    for card in ::alsa::card::Iter::new().filter_map(|card| card.ok()) {
+        let ctl = match ::alsa::Ctl::from_card(&card, false) {
+            Ok(ctl) => ctl,
+            Err(_) => continue
+        };
+        let card_info = match ctl.card_info() {
+            Ok(card_info) => card_info,
+            Err(_) => continue
+        };
+        let card_id = card_info.get_id().unwrap_or_default().to_string();
+        let card_name = card_info.get_name().unwrap_or_default().to_string();
+        for device in ::alsa::ctl::DeviceIter::new(&ctl) {
+            let pcm_name = match ctl.pcm_info(device as u32, 0, ::alsa::Direction::Capture) {
+                Ok(info) => info.get_name().unwrap_or_default().to_string(),
+                Err(_) => continue
+            };
+            for plugin in ["hw", "plughw"] {
+                pcms.push((format!("{}:CARD={},DEV={}", plugin, card_id, device), format!("{}, {}", card_name, pcm_name)));
+            }
+        }
+    }
+    pcms
+}
+
+/// Lists the capture PCMs of the sound cards, then the other ones ALSA advertises (default,
+/// dsnoop...), and probes each of them.
+pub fn discover_capture_devices() -> Result<Vec<CaptureDevice>, super::SoundCardError> {
+    let mut pcms = card_capture_pcms();
+    for device in capture_device_names()? {
+        if !pcms.iter().any(|(name, _)| *name == device.name) {
+            pcms.push((device.name, device.description));
+        }
+    }
+
+    Ok(pcms.into_iter()
+        .map(|(name, description)| {
+            let capabilities = probe_capture(&name);
+            CaptureDevice { name, description, capabilities }
+        })
+        .collect())
+}
+
 #[derive(Clone)]
 pub struct AlsaSoundCard<T: crate::math::Sample + ::alsa::pcm::IoFormat > {
     pub config: super::config::SoundCardConfig,
@@ -31,23 +192,27 @@ pub struct AlsaSoundCard<T: crate::math::Sample + ::alsa::pcm::IoFormat > {
 impl<T: crate::math::Sample + ::alsa::pcm::IoFormat> AlsaSoundCard<T> {
 
     
-    pub fn create_alsa_player(&self, channels: usize) -> AlsaPlayer<T> {
+    pub fn create_alsa_player(&self, channels: usize) -> Result<AlsaPlayer<T>, super::SoundCardError> {
         AlsaPlayer::<T>::new(self.clone(), channels)
     }
 
-    pub fn create_alsa_recorder(&self, channels: usize) -> AlsaRecorder<T> {
+    pub fn create_alsa_recorder(&self, channels: usize) -> Result<AlsaRecorder<T>, super::SoundCardError> {
         AlsaRecorder::<T>::new(self.clone(), channels)
     }
 
-    pub fn get_std_error(error: ::alsa::Error) -> std::io::Error {
-        std::io::Error::new(std::io::ErrorKind::Other, error)
+    /// Returns the error of `operation` on this sound card.
+    pub fn get_error(&self, operation: &str, error: ::alsa::Error) -> super::SoundCardError {
+        super::SoundCardError::from_alsa(&self.config.device_id, operation, error)
     }
 
     pub fn get_format(format: super::config::Format) -> alsa::pcm::Format {
         match format {
             super::config::Format::B16 => alsa::pcm::Format::S16LE,
             super::config::Format::B24 => alsa::pcm::Format::S243LE,
-            super::config::Format::B32 => alsa::pcm::Format::S32LE
+            super::config::Format::B32 => alsa::pcm::Format::S32LE,
+            super::config::Format::B24In32 => alsa::pcm::Format::S24LE,
+            super::config::Format::F32 => alsa::pcm::Format::FloatLE,
+            super::config::Format::U16 => alsa::pcm::Format::U16LE
         }
     }
 
@@ -55,47 +220,117 @@ impl<T: crate::math::Sample + ::alsa::pcm::IoFormat> AlsaSoundCard<T> {
         Self::get_format(self.config.format)
     }
 
-    fn setup_hardware<'a>(&'a self, pcm: &'a ::alsa::pcm::PCM, channels: usize) -> Result<::alsa::pcm::HwParams, ::alsa::Error> {
+    /// Opens the PCM of the device in `direction`. A device missing at this point was never
+    /// there, rather than unplugged.
+    fn open(&self, direction: ::alsa::Direction) -> Result<::alsa::pcm::PCM, super::SoundCardError> {
+        match ::alsa::pcm::PCM::new(self.config.device_id.as_str(), direction, false) {
+            Ok(pcm) => Ok(pcm),
+            Err(error) => match self.get_error("open the PCM", error) {
+                super::SoundCardError::DeviceUnplugged { device } => Err(super::SoundCardError::DeviceNotFound { device }),
+                error => Err(error)
+            }
+        }
+    }
+
+    fn setup_hardware<'a>(&'a self, pcm: &'a ::alsa::pcm::PCM, channels: usize) -> Result<::alsa::pcm::HwParams<'a>, super::SoundCardError> {
         
         let hwp: ::alsa::pcm::HwParams;
         let channels_u32 = channels as u32;
+        let device = self.config.device_id.clone();
 
         match alsa::pcm::HwParams::any(pcm) {
             Ok(hw_params) => hwp = hw_params,
-            Err(error) => return Err(error)
+            Err(error) => return Err(self.get_error("read the hardware parameters", error))
         };
 
         match hwp.set_channels(channels_u32) {
             Ok(_) => (),
-            Err(error) => return Err(error)
+            Err(error) if error.errno() as libc::c_int == libc::EINVAL => return Err(super::SoundCardError::UnsupportedChannels { device, channels }),
+            Err(error) => return Err(self.get_error("set the channels", error))
         };
         
         match hwp.set_format(self.get_current_format()) {
             Ok(_) => (),
-            Err(error) => return Err(error)
+            Err(error) if error.errno() as libc::c_int == libc::EINVAL => return Err(super::SoundCardError::UnsupportedFormat { device, format: self.config.format.label().to_string() }),
+            Err(error) => return Err(self.get_error("set the format", error))
+        };
+
+        // The range is read before the rate narrows it, to be reported if the rate is refused.
+        let supported = match (hwp.get_rate_min(), hwp.get_rate_max()) {
+            (Ok(min), Ok(max)) => Some((min as usize, max as usize)),
+            _ => None
         };
 
-        match hwp.set_rate(self.config.sampling_rate.value() as u32, alsa::ValueOr::Nearest) {
+        let rate = self.config.sampling_rate.value();
+        match hwp.set_rate(rate as u32, alsa::ValueOr::Nearest) {
             Ok(_) => (),
-            Err(error) => return Err(error)
+            Err(error) if error.errno() as libc::c_int == libc::EINVAL => return Err(super::SoundCardError::UnsupportedRate { device, rate, supported }),
+            Err(error) => return Err(self.get_error("set the sampling rate", error))
+        };
+
+        // The nearest rate is accepted silently, it must be the requested one to interpret the samples.
+        match hwp.get_rate() {
+            Ok(actual) if actual as usize == rate => (),
+            Ok(_) => return Err(super::SoundCardError::UnsupportedRate { device, rate, supported }),
+            Err(error) => return Err(self.get_error("read the sampling rate", error))
         };
 
         match hwp.set_access(alsa::pcm::Access::RWInterleaved) {
             Ok(_) => (),
-            Err(error) => return Err(error)
+            Err(error) => return Err(self.get_error("set interleaved access", error))
         };
 
         Ok(hwp)
     }
 
+    /// Configures `pcm` like `setup_hardware`, with the period and buffer sizes nearest to the
+    /// configured ones, and returns the sizes the device granted.
+    fn apply_hardware(&self, pcm: &::alsa::pcm::PCM, channels: usize) -> Result<super::config::BufferSizes, super::SoundCardError> {
+        let hwp = self.setup_hardware(pcm, channels)?;
+
+        let requested_period_size = self.config.period_size;
+        match hwp.set_period_size_near(requested_period_size as ::alsa::pcm::Frames, alsa::ValueOr::Nearest) {
+            Ok(_) => (),
+            Err(error) => return Err(self.get_error(&format!("set the period size to {}", requested_period_size), error))
+        };
+
+        let requested_buffer_size = self.config.buffer_size();
+        match hwp.set_buffer_size_near(requested_buffer_size as ::alsa::pcm::Frames) {
+            Ok(_) => (),
+            Err(error) => return Err(self.get_error(&format!("set the buffer size to {}", requested_buffer_size), error))
+        };
+
+        match pcm.hw_params(&hwp) {
+            Ok(_) => (),
+            Err(error) => return Err(self.get_error("apply the hardware parameters", error))
+        };
+
+        let period_size = match hwp.get_period_size() {
+            Ok(period_size) => period_size as usize,
+            Err(error) => return Err(self.get_error("read the period size", error))
+        };
+
+        let buffer_size = match hwp.get_buffer_size() {
+            Ok(buffer_size) => buffer_size as usize,
+            Err(error) => return Err(self.get_error("read the buffer size", error))
+        };
+
+        Ok(super::config::BufferSizes { requested_period_size, period_size, requested_buffer_size, buffer_size })
+    }
+
 }
 
 impl<T: crate::math::Sample + ::alsa::pcm::IoFormat> super::SoundCard<T> for AlsaSoundCard<T> {
-    fn new(config: super::config::SoundCardConfig) -> Self {
-        Self {
+    /// Fails when the samples of `T` do not have the configured format.
+    fn new(config: super::config::SoundCardConfig) -> Result<Self, super::SoundCardError> {
+        if T::FORMAT != Self::get_format(config.format) {
+            return Err(super::SoundCardError::UnsupportedFormat { device: config.device_id.clone(), format: config.format.label().to_string() });
+        }
+
+        Ok(Self {
             config: config,
             phantom: std::marker::PhantomData
-        }
+        })
     }
 
     fn config(&self) -> super::config::SoundCardConfig {
@@ -108,47 +343,47 @@ impl<T: crate::math::Sample + ::alsa::pcm::IoFormat> super::SoundCard<T> for Als
 pub struct AlsaPlayer<T: crate::math::Sample + ::alsa::pcm::IoFormat> {
     pub sound_card: AlsaSoundCard<T>,
     pub channels: usize,
+    /// Period and buffer sizes negotiated with the device.
+    pub buffer_sizes: super::config::BufferSizes,
     alsa_pcm: ::alsa::pcm::PCM
 }
 
 impl<T: crate::math::Sample + ::alsa::pcm::IoFormat> AlsaPlayer<T> {
-    fn new(sound_card: AlsaSoundCard<T>, channels: usize) -> Self {
-        let data;
-        match ::alsa::pcm::PCM::new(&(sound_card.config.device_id.as_str()), ::alsa::Direction::Playback, false) {
-            Ok(pcm) => { data = AlsaPlayer::<T> {
-                                sound_card: sound_card,
-                                channels: channels,
-                                alsa_pcm: pcm
-                            };
-                            {
-                                let hwp;
-                                match data.sound_card.setup_hardware(&data.alsa_pcm, data.channels) {
-                                    Ok(hw_params) => hwp = hw_params,
-                                    Err(error) => panic!("Could not setup hardware for PCM capture device '{}': {}", data.sound_card.config.device_id, error)
-                                };
-                        
-                                match data.alsa_pcm.hw_params(&hwp) {
-                                    Ok(_) => (),
-                                    Err(error) => panic!("Could not set hardware for PCM capture device '{}': {}", data.sound_card.config.device_id, error)
-                                };
-
-                                let swp = &data.alsa_pcm.sw_params_current().unwrap();
-                                swp.set_start_threshold(hwp.get_buffer_size().unwrap()).unwrap();
-                                
-
-                            }
-                            return data;
-            },
-            Err(error) => panic!("Could not initialize PCM capture device '{}': {}", sound_card.config.device_id, error)
+    fn new(sound_card: AlsaSoundCard<T>, channels: usize) -> Result<Self, super::SoundCardError> {
+        let alsa_pcm = sound_card.open(::alsa::Direction::Playback)?;
+        let buffer_sizes = sound_card.apply_hardware(&alsa_pcm, channels)?;
+
+        {
+            let swp = match alsa_pcm.sw_params_current() {
+                Ok(swp) => swp,
+                Err(error) => return Err(sound_card.get_error("read the software parameters", error))
+            };
+
+            match swp.set_start_threshold(buffer_sizes.buffer_size as ::alsa::pcm::Frames) {
+                Ok(_) => (),
+                Err(error) => return Err(sound_card.get_error("set the start threshold", error))
+            };
+
+            match alsa_pcm.sw_params(&swp) {
+                Ok(_) => (),
+                Err(error) => return Err(sound_card.get_error("apply the software parameters", error))
+            };
         }
+
+        Ok(AlsaPlayer::<T> {
+            sound_card,
+            channels,
+            buffer_sizes,
+            alsa_pcm
+        })
     }
 }
 
 impl<T: crate::math::Sample + ::alsa::pcm::IoFormat> AlsaSoundCardLink for AlsaPlayer<T> {
-    fn link<'a, U: AlsaSoundCardLink>(&'a mut self, other: &'a mut U) -> Result<(), std::io::Error> {
+    fn link<'a, U: AlsaSoundCardLink>(&'a mut self, other: &'a mut U) -> Result<(), super::SoundCardError> {
         match self.alsa_pcm.link(&other.get_pcm()) {
             Ok(_) => Ok(()),
-            Err(_) => Err(std::io::Error::new(std::io::ErrorKind::Other, "Error occurred while linking to other pcm."))
+            Err(error) => Err(self.sound_card.get_error("link to the other PCM", error))
         }
     }
 
@@ -160,14 +395,14 @@ impl<T: crate::math::Sample + ::alsa::pcm::IoFormat> AlsaSoundCardLink for AlsaP
 
 impl<T: crate::math::Sample + ::alsa::pcm::IoFormat> super::SoundCardPlayer<T> for AlsaPlayer<T> {
 
-    fn wait_for_finish(&mut self) -> Result<(), std::io::Error> {
+    fn wait_for_finish(&mut self) -> Result<(), super::SoundCardError> {
         match self.alsa_pcm.drain() {
             Ok(()) => Ok(()),
-            Err(error) => Err(AlsaSoundCard::<T>::get_std_error(error))
+            Err(error) => Err(self.sound_card.get_error("drain the playback", error))
         }
     }
 
-    fn play(&mut self, data: &[super::ChannelData<T>]) -> Result<(), std::io::Error> {
+    fn play(&mut self, data: &[super::ChannelData<T>]) -> Result<(), super::SoundCardError> {
 
         let mut i: usize = 0;
         if data.len() < 1 {
@@ -175,7 +410,7 @@ impl<T: crate::math::Sample + ::alsa::pcm::IoFormat> super::SoundCardPlayer<T> f
         }
 
         if self.channels != data.len() {
-            panic!("Data (len: {}) does not contain enough channels ({}).", data.len(), self.channels);
+            return Err(super::SoundCardError::ChannelMismatch { device: self.sound_card.config.device_id.clone(), expected: self.channels, actual: data.len() });
         }
 
         let mut min_length: usize = usize::MAX;
@@ -202,16 +437,16 @@ impl<T: crate::math::Sample + ::alsa::pcm::IoFormat> super::SoundCardPlayer<T> f
 
         match self.alsa_pcm.io_checked::<T>() {
             Ok(io) => pcm_io = io,
-            Err(error) => return Err(AlsaSoundCard::<T>::get_std_error(error))
+            Err(error) => return Err(self.sound_card.get_error("set up the playback", error))
         };
 
 
         let mut total_frames_written: usize = 0;
-        let buffer_length = super::config::SamplingRate::SAMPLING_RATE_192000 / 50;
-        while total_frames_written < interleaved_data.len() {
+        let buffer_length = self.buffer_sizes.period_size;
+        while total_frames_written < num_frames {
             match pcm_io.writei(&interleaved_data[total_frames_written*self.channels..std::cmp::min(interleaved_data.len(), (total_frames_written+buffer_length)*self.channels)]) {
                 Ok(frames_written) => total_frames_written += frames_written,
-                Err(error) => return Err(AlsaSoundCard::<T>::get_std_error(error))
+                Err(error) => return Err(self.sound_card.get_error("play", error))
             };
         }
 
@@ -220,91 +455,184 @@ impl<T: crate::math::Sample + ::alsa::pcm::IoFormat> super::SoundCardPlayer<T> f
 }
 
 
-pub struct AlsaRecorder<T: crate::math::Sample + ::alsa::pcm::IoFormat> {
+/// Capture operations of [`AlsaRecorder`] on its PCM, simulated by the tests.
+pub trait CapturePcm<T: crate::math::Sample + ::alsa::pcm::IoFormat>: Sized {
+    /// Opens and configures the PCM of `sound_card` again, as [`AlsaRecorder::open_capture`].
+    fn reopen(&self, sound_card: &AlsaSoundCard<T>, channels: usize) -> Result<(Self, bool, super::config::BufferSizes), super::SoundCardError>;
+    fn prepare(&self) -> ::alsa::Result<()>;
+    fn stop(&self) -> ::alsa::Result<()>;
+    /// Reads interleaved frames into `buffer` and returns how many were read.
+    fn read(&self, buffer: &mut [T]) -> ::alsa::Result<usize>;
+    fn recover(&self, errno: libc::c_int) -> ::alsa::Result<()>;
+    /// Estimates the UTC time the last frame returned by the latest read was captured at.
+    fn last_frame_time(&self, realtime_timestamps: bool, sampling_rate: usize) -> chrono::DateTime<chrono::Utc>;
+}
+
+impl<T: crate::math::Sample + ::alsa::pcm::IoFormat> CapturePcm<T> for ::alsa::pcm::PCM {
+    fn reopen(&self, sound_card: &AlsaSoundCard<T>, channels: usize) -> Result<(Self, bool, super::config::BufferSizes), super::SoundCardError> {
+        AlsaRecorder::<T>::open_capture(sound_card, channels)
+    }
+
+    fn prepare(&self) -> ::alsa::Result<()> {
+        ::alsa::pcm::PCM::prepare(self)
+    }
+
+    fn stop(&self) -> ::alsa::Result<()> {
+        ::alsa::pcm::PCM::drop(self)
+    }
+
+    fn read(&self, buffer: &mut [T]) -> ::alsa::Result<usize> {
+        self.io_checked::<T>()?.readi(buffer)
+    }
+
+    fn recover(&self, errno: libc::c_int) -> ::alsa::Result<()> {
+        ::alsa::pcm::PCM::recover(self, errno, true)
+    }
+
+    /// The frames delayed in the buffer were all captured after the last one read.
+    fn last_frame_time(&self, realtime_timestamps: bool, sampling_rate: usize) -> chrono::DateTime<chrono::Utc> {
+        if realtime_timestamps {
+            if let Ok(status) = self.status() {
+                let htstamp = status.get_htstamp();
+                if htstamp.tv_sec != 0 || htstamp.tv_nsec != 0 {
+                    if let Some(time) = chrono::DateTime::from_timestamp(htstamp.tv_sec, htstamp.tv_nsec as u32) {
+                        return time - super::frames_duration(status.get_delay(), sampling_rate);
+                    }
+                }
+            }
+        }
+
+        let now = chrono::Utc::now();
+        now - super::frames_duration(self.delay().unwrap_or(0), sampling_rate)
+    }
+}
+
+pub struct AlsaRecorder<T: crate::math::Sample + ::alsa::pcm::IoFormat, P: CapturePcm<T> = ::alsa::pcm::PCM> {
     pub sound_card: AlsaSoundCard<T>,
     pub channels: usize,
-    alsa_pcm: ::alsa::pcm::PCM,
-    buffer: [T; super::config::SamplingRate::SAMPLING_RATE_192000 / 50]
+    /// Overruns recovered from since the recorder was created.
+    pub xruns: usize,
+    /// Frames estimated lost to the overruns.
+    pub dropped_frames: usize,
+    /// Times the device was reopened after it was unplugged.
+    pub reconnections: usize,
+    /// Period and buffer sizes negotiated with the device, when it was last opened.
+    pub buffer_sizes: super::config::BufferSizes,
+    alsa_pcm: P,
+    /// Holds one period of interleaved frames.
+    buffer: Vec<T>,
+    realtime_timestamps: bool,
+    unplugged: bool,
+    reconnect_at: Option<std::time::Instant>,
+    reconnect_delay: std::time::Duration
 }
 
 
 impl<T: crate::math::Sample + ::alsa::pcm::IoFormat> AlsaRecorder<T> {
-    fn new(sound_card: AlsaSoundCard<T>, channels: usize) -> Self {
-        let data;
-        match ::alsa::pcm::PCM::new(&(sound_card.config.device_id.as_str()), ::alsa::Direction::Capture, false) {
-            Ok(pcm) => { data = AlsaRecorder::<T> {
-                                sound_card: sound_card,
-                                channels: channels,
-                                alsa_pcm: pcm,
-                                buffer: [T::default(); super::config::SamplingRate::SAMPLING_RATE_192000 / 50]
-                            };
-                            {
-                                let sampling_rate_value = data.sound_card.config.sampling_rate.value();
-                                let hwp;
-                                match data.sound_card.setup_hardware(&data.alsa_pcm, data.channels) {
-                                    Ok(hw_params) => hwp = hw_params,
-                                    Err(error) => panic!("Could not setup hardware for PCM capture device '{}': {}", data.sound_card.config.device_id, error)
-                                };
-
-                                match hwp.set_period_size(data.sound_card.config.period_size as i64, alsa::ValueOr::Nearest) {
-                                    Ok(_) => (),
-                                    Err(error) => panic!("Could not set period size to '{}' for PCM capture device '{}': {}", data.sound_card.config.period_size as i64, data.sound_card.config.device_id, error)
-                                };
-                        
-                                let buffer_size = data.sound_card.config.period_size as i64 * 8;
-                                match hwp.set_buffer_size(buffer_size) {
-                                    Ok(_) => (),
-                                    Err(error) => panic!("Could not set buffer size to '{}' for PCM capture device '{}': {}", buffer_size, data.sound_card.config.device_id, error)
-                                };
-                        
-                                match data.alsa_pcm.hw_params(&hwp) {
-                                    Ok(_) => (),
-                                    Err(error) => panic!("Could not set hardware for PCM capture device '{}': {}", data.sound_card.config.device_id, error)
-                                };
-                                
-                                match data.alsa_pcm.start() {
-                                    Ok(_) => (),
-                                    Err(error) => panic!("Could not start PCM capture device '{}': {}", data.sound_card.config.device_id, error)
-                                };
-                                
-                                match data.alsa_pcm.drop() {
-                                    Ok(_) => (),
-                                    Err(error) => panic!("Could not stop PCM capture device '{}' after starting: {}", data.sound_card.config.device_id, error)
-                                };
-                            }
-                            return data;
-            },
-            Err(error) => panic!("Could not initialize PCM capture device '{}': {}", sound_card.config.device_id, error)
-        }
+    fn new(sound_card: AlsaSoundCard<T>, channels: usize) -> Result<Self, super::SoundCardError> {
+        let (alsa_pcm, realtime_timestamps, buffer_sizes) = Self::open_capture(&sound_card, channels)?;
+
+        Ok(AlsaRecorder::<T> {
+            sound_card,
+            channels,
+            xruns: 0,
+            dropped_frames: 0,
+            reconnections: 0,
+            buffer_sizes,
+            alsa_pcm,
+            buffer: vec![T::default(); buffer_sizes.period_size * channels],
+            realtime_timestamps,
+            unplugged: false,
+            reconnect_at: None,
+            reconnect_delay: Self::MIN_RECONNECT_DELAY
+        })
     }
-}
 
-impl<T: crate::math::Sample + ::alsa::pcm::IoFormat> AlsaSoundCardLink for AlsaRecorder<T> {
-    fn link<'a, U: AlsaSoundCardLink>(&'a mut self, other: &'a mut U) -> Result<(), std::io::Error> {
-        match self.alsa_pcm.link(&other.get_pcm()) {
-            Ok(_) => Ok(()),
-            Err(_) => Err(std::io::Error::new(std::io::ErrorKind::Other, "Error occurred while linking to other pcm."))
+    /// Opens and configures the capture PCM. Also returns whether its timestamps are on the
+    /// realtime clock and the negotiated buffer sizes.
+    fn open_capture(sound_card: &AlsaSoundCard<T>, channels: usize) -> Result<(::alsa::pcm::PCM, bool, super::config::BufferSizes), super::SoundCardError> {
+        let alsa_pcm = sound_card.open(::alsa::Direction::Capture)?;
+        let buffer_sizes = sound_card.apply_hardware(&alsa_pcm, channels)?;
+        let realtime_timestamps;
+
+        {
+            // Timestamps on the realtime clock date the frames as the hardware captured
+            // them; devices without them fall back to the system time of each read.
+            realtime_timestamps = match alsa_pcm.sw_params_current() {
+                Ok(swp) => swp.set_tstamp_mode(true).is_ok()
+                    && swp.set_tstamp_type(::alsa::pcm::TstampType::Gettimeofday).is_ok()
+                    && alsa_pcm.sw_params(&swp).is_ok(),
+                Err(_) => false
+            };
+
+            match alsa_pcm.start() {
+                Ok(_) => (),
+                Err(error) => return Err(sound_card.get_error("start the capture", error))
+            };
+
+            match alsa_pcm.drop() {
+                Ok(_) => (),
+                Err(error) => return Err(sound_card.get_error("stop the capture after starting it", error))
+            };
         }
-    }
 
-    fn get_pcm<'a>(&'a mut self) -> &'a alsa::pcm::PCM {
-        return &self.alsa_pcm;
+        Ok((alsa_pcm, realtime_timestamps, buffer_sizes))
     }
 }
 
-impl<T: crate::math::Sample + ::alsa::pcm::IoFormat> super::SoundCardRecorder<T> for AlsaRecorder<T> {
+impl<T: crate::math::Sample + ::alsa::pcm::IoFormat, P: CapturePcm<T>> AlsaRecorder<T, P> {
+    /// Wait before the first attempt to reopen an unplugged device, doubled after each failure.
+    const MIN_RECONNECT_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+    const MAX_RECONNECT_DELAY: std::time::Duration = std::time::Duration::from_secs(60);
 
-    fn record(&mut self, milliseconds: usize) -> Result<Vec<super::ChannelData<T>>, std::io::Error> {
+    /// Returns the error of `operation`, remembering when it means the device was unplugged.
+    fn failure(&mut self, operation: &str, error: ::alsa::Error) -> super::SoundCardError {
+        let error = self.sound_card.get_error(operation, error);
+        if let super::SoundCardError::DeviceUnplugged { .. } = error {
+            self.unplugged = true;
+        }
+        error
+    }
 
-        let num_frames = self.sound_card.config.sampling_rate.value() * milliseconds / 1000;
+    /// Reopens the device when it was unplugged. Attempts are spaced by a delay doubling up to
+    /// `MAX_RECONNECT_DELAY`; until the device is back, `DeviceUnplugged` is returned.
+    fn reconnect(&mut self) -> Result<(), super::SoundCardError> {
+        if !self.unplugged {
+            return Ok(());
+        }
 
-        let pcm_io;
+        if let Some(reconnect_at) = self.reconnect_at {
+            if std::time::Instant::now() < reconnect_at {
+                return Err(super::SoundCardError::DeviceUnplugged { device: self.sound_card.config.device_id.clone() });
+            }
+        }
 
-        match self.alsa_pcm.io_checked::<T>() {
-            Ok(io) => pcm_io = io,
-            Err(error) => return Err(AlsaSoundCard::<T>::get_std_error(error))
-        };
+        match self.alsa_pcm.reopen(&self.sound_card, self.channels) {
+            Ok((alsa_pcm, realtime_timestamps, buffer_sizes)) => {
+                self.alsa_pcm = alsa_pcm;
+                self.realtime_timestamps = realtime_timestamps;
+                self.buffer_sizes = buffer_sizes;
+                self.buffer = vec![T::default(); buffer_sizes.period_size * self.channels];
+                self.unplugged = false;
+                self.reconnect_at = None;
+                self.reconnect_delay = Self::MIN_RECONNECT_DELAY;
+                self.reconnections += 1;
+                Ok(())
+            },
+            Err(error) => {
+                self.reconnect_at = Some(std::time::Instant::now() + self.reconnect_delay);
+                self.reconnect_delay = std::cmp::min(self.reconnect_delay * 2, Self::MAX_RECONNECT_DELAY);
+                match error {
+                    super::SoundCardError::DeviceNotFound { device } => Err(super::SoundCardError::DeviceUnplugged { device }),
+                    error => Err(error)
+                }
+            }
+        }
+    }
 
+    /// Reads `num_frames` frames from the prepared PCM. Overruns are recovered from and the
+    /// frames they lost are counted in the returned channels.
+    fn read_chunk(&mut self, num_frames: usize) -> Result<Vec<super::ChannelData<T>>, super::SoundCardError> {
         let mut total_frames_read: usize = 0;
         let mut data = Vec::<super::ChannelData<T>>::with_capacity(self.channels);
         let mut i: usize = 0;
@@ -313,105 +641,415 @@ impl<T: crate::math::Sample + ::alsa::pcm::IoFormat> super::SoundCardRecorder<T>
             data.push(super::ChannelData::<T>::new(i + 1, Vec::<T>::with_capacity(num_frames)));
             i += 1;
         }
-    
-        match self.alsa_pcm.prepare() {
-            Ok(_) => (),
-            Err(error) => return Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, format!("Could not prepare PCM capture device '{}': {}", self.sound_card.config.device_id, error)))
-        };
 
-        let mut j: usize;
-        let buffer_len = self.buffer.len();
+        let sampling_rate = self.sound_card.config.sampling_rate.value();
+        let mut record_start: Option<chrono::DateTime<chrono::Utc>> = None;
+        let mut record_end: Option<chrono::DateTime<chrono::Utc>> = None;
+        let mut dropped_frames: usize = 0;
+        let mut recovered = false;
         while total_frames_read < num_frames {
-            match pcm_io.readi(&mut self.buffer) {
+            match self.alsa_pcm.read(&mut self.buffer) {
                 Ok(frames_read) => {
-                    total_frames_read += frames_read;
-                    i = 0;
-                    while i < buffer_len / 2 {
-                        j = 0;
-                        while j < self.channels {
-                            data[j].channel_data.push(self.buffer[(2*i)+j]);
-                            j += 1;
+                    let last_frame = self.alsa_pcm.last_frame_time(self.realtime_timestamps, sampling_rate);
+                    let first_frame = last_frame - super::frames_duration(frames_read as i64 - 1, sampling_rate);
+                    if recovered {
+                        // The frames between the last one read before the overrun and the first
+                        // one read after it were lost.
+                        if let Some(previous) = record_end {
+                            let gap = (first_frame - previous).num_microseconds().unwrap_or(0) * sampling_rate as i64 / 1_000_000 - 1;
+                            dropped_frames += std::cmp::max(gap, 0) as usize;
                         }
-                        i += 1;
+                        recovered = false;
+                    }
+                    if record_start.is_none() {
+                        record_start = Some(first_frame);
+                    }
+
+                    // Frames read past the requested duration are dropped.
+                    let frames_kept = std::cmp::min(frames_read, num_frames - total_frames_read);
+                    record_end = Some(last_frame - super::frames_duration((frames_read - frames_kept) as i64, sampling_rate));
+                    super::deinterleave(&self.buffer[..frames_kept * self.channels], &mut data);
+                    total_frames_read += frames_kept;
+                }
+                Err(error) => {
+                    let errno = error.errno() as libc::c_int;
+                    if (errno == libc::EPIPE || errno == libc::ESTRPIPE) && self.alsa_pcm.recover(errno).is_ok() {
+                        self.xruns += 1;
+                        recovered = true;
+                        continue;
                     }
+                    let _ = self.alsa_pcm.stop();
+                    return Err(self.failure("record", error))
                 }
-                Err(error) => return Err(AlsaSoundCard::<T>::get_std_error(error))
             };
         }
 
-        let finished = std::time::Instant::now();
+        self.dropped_frames += dropped_frames;
         i = 0;
         while i < self.channels {
-            data[i].record_end = Some(finished);
+            data[i].record_start = record_start;
+            data[i].record_end = record_end;
+            data[i].dropped_frames = dropped_frames;
+            i += 1;
+        }
+
+        Ok(data)
+    }
+}
+
+impl<T: crate::math::Sample + ::alsa::pcm::IoFormat> AlsaRecorder<T> {
+    /// Records while `player` plays `data`, one period of each in turn, and returns the frames
+    /// captured once the playback buffer was filled: as many as `data` holds past the playback
+    /// buffer size. The PCMs are expected linked by [`AlsaSoundCardLink::link`], so that filling
+    /// the playback buffer starts the capture on the same frame; the capture is started on its
+    /// own otherwise.
+    pub fn record_while_playing<U: crate::math::Sample + ::alsa::pcm::IoFormat>(&mut self, player: &mut AlsaPlayer<U>, data: &[super::ChannelData<U>]) -> Result<Vec<super::ChannelData<T>>, super::SoundCardError> {
+        if data.len() != player.channels {
+            return Err(super::SoundCardError::ChannelMismatch { device: player.sound_card.config.device_id.clone(), expected: player.channels, actual: data.len() });
+        }
+
+        let length = data.iter().map(|channel| channel.channel_data.len()).min().unwrap_or(0);
+        let mut interleaved_data: Vec<U> = Vec::<U>::with_capacity(length * player.channels);
+        let mut i: usize = 0;
+        while i < length {
+            for channel in data.iter() {
+                interleaved_data.push(channel.channel_data[i]);
+            }
+            i += 1;
+        }
+
+        let buffer_size = std::cmp::min(player.buffer_sizes.buffer_size, length);
+        let num_frames = length - buffer_size;
+        let mut captured: Vec<super::ChannelData<T>> = (0..self.channels).map(|i| super::ChannelData::<T>::new(i + 1, Vec::<T>::with_capacity(num_frames))).collect();
+
+        match player.alsa_pcm.prepare() {
+            Ok(_) => (),
+            Err(error) => return Err(player.sound_card.get_error("prepare the playback", error))
+        };
+        match self.alsa_pcm.prepare() {
+            Ok(_) => (),
+            Err(error) => return Err(self.failure("prepare the capture", error))
+        };
+
+        let playback_io = match player.alsa_pcm.io_checked::<U>() {
+            Ok(io) => io,
+            Err(error) => return Err(player.sound_card.get_error("set up the playback", error))
+        };
+        let capture_io = match self.alsa_pcm.io_checked::<T>() {
+            Ok(io) => io,
+            Err(error) => return Err(self.sound_card.get_error("set up the capture", error))
+        };
+
+        // A full playback buffer reaches the start threshold of the player.
+        let mut total_frames_written: usize = 0;
+        while total_frames_written < buffer_size {
+            match playback_io.writei(&interleaved_data[total_frames_written * player.channels..buffer_size * player.channels]) {
+                Ok(frames_written) => total_frames_written += frames_written,
+                Err(error) => return Err(player.sound_card.get_error("play", error))
+            };
+        }
+        if self.alsa_pcm.state() != ::alsa::pcm::State::Running {
+            match self.alsa_pcm.start() {
+                Ok(_) => (),
+                Err(error) => return Err(self.sound_card.get_error("start the capture", error))
+            };
         }
 
+        let mut total_frames_read: usize = 0;
+        while total_frames_read < num_frames {
+            let frames_read = match capture_io.readi(&mut self.buffer) {
+                Ok(frames_read) => frames_read,
+                Err(error) => {
+                    let _ = self.alsa_pcm.drop();
+                    let _ = player.alsa_pcm.drop();
+                    return Err(self.sound_card.get_error("record", error));
+                }
+            };
+            let frames_kept = std::cmp::min(frames_read, num_frames - total_frames_read);
+            super::deinterleave(&self.buffer[..frames_kept * self.channels], &mut captured);
+            total_frames_read += frames_kept;
+
+            // Tops the playback buffer up by what the capture consumed.
+            let end = std::cmp::min(total_frames_written + frames_read, length);
+            while total_frames_written < end {
+                match playback_io.writei(&interleaved_data[total_frames_written * player.channels..end * player.channels]) {
+                    Ok(frames_written) => total_frames_written += frames_written,
+                    Err(error) => {
+                        let _ = self.alsa_pcm.drop();
+                        let _ = player.alsa_pcm.drop();
+                        return Err(player.sound_card.get_error("play", error));
+                    }
+                };
+            }
+        }
+
+        // Stopping one of linked PCMs stops both, the playback is stopped again when alone.
         match self.alsa_pcm.drop() {
             Ok(_) => (),
-            Err(error) => return Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, format!("Could not drop PCM capture device '{}'  after capture: {}", self.sound_card.config.device_id, error)))
+            Err(error) => return Err(self.sound_card.get_error("stop the capture", error))
         };
+        match player.alsa_pcm.drop() {
+            Ok(_) => Ok(captured),
+            Err(error) => Err(player.sound_card.get_error("stop the playback", error))
+        }
+    }
+}
 
-        Ok(data)
+impl<T: crate::math::Sample + ::alsa::pcm::IoFormat> AlsaSoundCardLink for AlsaRecorder<T> {
+    fn link<'a, U: AlsaSoundCardLink>(&'a mut self, other: &'a mut U) -> Result<(), super::SoundCardError> {
+        match self.alsa_pcm.link(&other.get_pcm()) {
+            Ok(_) => Ok(()),
+            Err(error) => Err(self.sound_card.get_error("link to the other PCM", error))
+        }
     }
 
-    fn record_loop(&mut self, milliseconds: usize, each: &mut dyn FnMut(Vec<super::ChannelData<T>>)) {
+    fn get_pcm<'a>(&'a mut self) -> &'a alsa::pcm::PCM {
+        return &self.alsa_pcm;
+    }
+}
 
-        let num_frames = self.sound_card.config.sampling_rate.value() * milliseconds / 1000;
+impl<T: crate::math::Sample + ::alsa::pcm::IoFormat, P: CapturePcm<T>> super::SoundCardRecorder<T> for AlsaRecorder<T, P> {
 
-        let pcm_io;
+    /// Reopens the device first when it was unplugged, see [`AlsaRecorder::reconnect`].
+    fn record(&mut self, milliseconds: usize) -> Result<Vec<super::ChannelData<T>>, super::SoundCardError> {
+        self.reconnect()?;
 
-        match self.alsa_pcm.io_checked::<T>() {
-            Ok(io) => pcm_io = io,
-            Err(error) => panic!("Error occurred while setting up io for recording: {}", error)
-        };
+        let num_frames = self.sound_card.config.sampling_rate.value() * milliseconds / 1000;
 
         match self.alsa_pcm.prepare() {
             Ok(_) => (),
-            Err(error) => panic!("Could not prepare PCM capture device '{}': {}", self.sound_card.config.device_id, error)
+            Err(error) => return Err(self.failure("prepare the capture", error))
         };
 
-        let mut total_frames_read: usize;
-        let mut data: Vec<super::ChannelData<T>>;
-        let mut i: usize;
-        let mut j: usize;
-        let buffer_len = self.buffer.len();
+        let data = self.read_chunk(num_frames)?;
+
+        match self.alsa_pcm.stop() {
+            Ok(_) => (),
+            Err(error) => return Err(self.failure("stop the capture", error))
+        };
+
+        Ok(data)
+    }
+
+    /// Keeps reopening the device while it is unplugged, so the chunks resume once it is back;
+    /// the gap shows in their `record_start`. Any other failure to reopen it is returned.
+    fn record_loop(&mut self, milliseconds: usize, each: &mut dyn FnMut(Vec<super::ChannelData<T>>) -> bool) -> Result<(), super::SoundCardError> {
+
+        let num_frames = self.sound_card.config.sampling_rate.value() * milliseconds / 1000;
+        let mut prepared = false;
+
         loop {
-            data = Vec::<super::ChannelData<T>>::with_capacity(self.channels);
-            total_frames_read = 0;
-            
-            i = 0;
-            while i < self.channels {
-                data.push(super::ChannelData::<T>::new(i + 1, Vec::<T>::with_capacity(num_frames)));
-                i += 1;
-            }
+            if !prepared {
+                match self.reconnect() {
+                    Ok(()) => (),
+                    Err(super::SoundCardError::DeviceUnplugged { .. }) => {
+                        let reconnect_at = self.reconnect_at.unwrap_or_else(std::time::Instant::now);
+                        std::thread::sleep(reconnect_at.saturating_duration_since(std::time::Instant::now()));
+                        continue;
+                    },
+                    Err(error) => return Err(error)
+                };
 
-            while total_frames_read < num_frames {
-                match pcm_io.readi(&mut self.buffer) {
-                    Ok(frames_read) => {
-                        total_frames_read += frames_read;
-                        i = 0;
-                        while i < buffer_len / 2 {
-                            j = 0;
-                            while j < self.channels {
-                                data[j].channel_data.push(self.buffer[(2*i)+j]);
-                                j += 1;
-                            }
-                            i += 1;
-                        }
-                    }
-                    Err(error) => {
-                        let _ = self.alsa_pcm.drop();
-                        panic!("Error occurred while recording: {}", error)
+                match self.alsa_pcm.prepare() {
+                    Ok(_) => (),
+                    Err(error) => match self.failure("prepare the capture", error) {
+                        super::SoundCardError::DeviceUnplugged { .. } => continue,
+                        error => return Err(error)
                     }
                 };
+                prepared = true;
             }
 
-            let finished = std::time::Instant::now();
-            i = 0;
-            while i < self.channels {
-                data[i].record_end = Some(finished);
+            match self.read_chunk(num_frames) {
+                Ok(data) => {
+                    if !each(data) {
+                        break;
+                    }
+                },
+                Err(super::SoundCardError::DeviceUnplugged { .. }) => prepared = false,
+                Err(error) => return Err(error)
+            };
+        }
+
+        match self.alsa_pcm.stop() {
+            Ok(_) => Ok(()),
+            Err(error) => Err(self.failure("stop the capture", error))
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sound_card::SoundCardRecorder;
+
+    const CHANNELS: usize = 2;
+    /// Frames of a period, a whole number of microseconds at 48 kHz like the gaps below.
+    const PERIOD: usize = 96;
+
+    /// What the simulated device does on a read.
+    #[derive(Debug, Clone, Copy)]
+    enum Read {
+        /// Returns up to that many frames, one period by default.
+        Frames(usize),
+        /// Loses that many frames and fails with EPIPE.
+        Overrun(usize),
+        /// Fails with ENODEV.
+        Unplugged
+    }
+
+    /// Script and position of the simulated device, kept across its reconnections.
+    struct Device {
+        reads: std::collections::VecDeque<Read>,
+        /// Errno of each attempt to reopen the device, 0 to succeed.
+        opens: std::collections::VecDeque<libc::c_int>,
+        /// Index of the next frame captured.
+        frame: i64,
+        start: chrono::DateTime<chrono::Utc>
+    }
+
+    struct FakePcm {
+        device: std::rc::Rc<std::cell::RefCell<Device>>
+    }
+
+    /// Sample of `channel` at `frame`, so every sample tells where it was captured.
+    fn sample(frame: i64, channel: usize) -> i16 {
+        ((frame % 10000) * CHANNELS as i64 + channel as i64) as i16
+    }
+
+    fn buffer_sizes() -> crate::sound_card::config::BufferSizes {
+        crate::sound_card::config::BufferSizes { requested_period_size: PERIOD, period_size: PERIOD, requested_buffer_size: 4 * PERIOD, buffer_size: 4 * PERIOD }
+    }
+
+    impl CapturePcm<i16> for FakePcm {
+        fn reopen(&self, sound_card: &AlsaSoundCard<i16>, _channels: usize) -> Result<(Self, bool, crate::sound_card::config::BufferSizes), crate::sound_card::SoundCardError> {
+            match self.device.borrow_mut().opens.pop_front().unwrap_or(0) {
+                0 => Ok((FakePcm { device: self.device.clone() }, false, buffer_sizes())),
+                errno => Err(sound_card.get_error("open the PCM", ::alsa::Error::new("snd_pcm_open", errno)))
+            }
+        }
+
+        fn prepare(&self) -> ::alsa::Result<()> {
+            Ok(())
+        }
+
+        fn stop(&self) -> ::alsa::Result<()> {
+            Ok(())
+        }
+
+        fn read(&self, buffer: &mut [i16]) -> ::alsa::Result<usize> {
+            let mut device = self.device.borrow_mut();
+            match device.reads.pop_front().unwrap_or(Read::Frames(PERIOD)) {
+                Read::Frames(frames) => {
+                    let frames = std::cmp::min(frames, buffer.len() / CHANNELS);
+                    let mut i = 0usize;
+                    while i < frames * CHANNELS {
+                        buffer[i] = sample(device.frame + (i / CHANNELS) as i64, i % CHANNELS);
+                        i += 1;
+                    }
+                    device.frame += frames as i64;
+                    Ok(frames)
+                },
+                Read::Overrun(lost) => {
+                    device.frame += lost as i64;
+                    Err(::alsa::Error::new("snd_pcm_readi", libc::EPIPE))
+                },
+                Read::Unplugged => Err(::alsa::Error::new("snd_pcm_readi", libc::ENODEV))
             }
+        }
+
+        fn recover(&self, errno: libc::c_int) -> ::alsa::Result<()> {
+            if errno == libc::EPIPE { Ok(()) } else { Err(::alsa::Error::new("snd_pcm_recover", errno)) }
+        }
 
-            each(data);
+        fn last_frame_time(&self, _realtime_timestamps: bool, sampling_rate: usize) -> chrono::DateTime<chrono::Utc> {
+            let device = self.device.borrow();
+            device.start + crate::sound_card::frames_duration(device.frame - 1, sampling_rate)
         }
     }
-}
\ No newline at end of file
+
+    fn fake_recorder(reads: &[Read], opens: &[libc::c_int]) -> AlsaRecorder<i16, FakePcm> {
+        use crate::sound_card::config::{Format, SamplingRate, SoundCardConfig};
+        use crate::sound_card::SoundCard;
+
+        let device = Device {
+            reads: reads.iter().copied().collect(),
+            opens: opens.iter().copied().collect(),
+            frame: 0,
+            start: chrono::DateTime::from_timestamp(1_792_000_000, 0).unwrap()
+        };
+        AlsaRecorder {
+            sound_card: AlsaSoundCard::<i16>::new(SoundCardConfig::new("fake", Format::B16, SamplingRate::Hz48000, PERIOD)).unwrap(),
+            channels: CHANNELS,
+            xruns: 0,
+            dropped_frames: 0,
+            reconnections: 0,
+            buffer_sizes: buffer_sizes(),
+            alsa_pcm: FakePcm { device: std::rc::Rc::new(std::cell::RefCell::new(device)) },
+            buffer: vec![0i16; PERIOD * CHANNELS],
+            realtime_timestamps: false,
+            unplugged: false,
+            reconnect_at: None,
+            reconnect_delay: AlsaRecorder::<i16, FakePcm>::MIN_RECONNECT_DELAY
+        }
+    }
+
+    #[test]
+    fn read_chunk_deinterleaves_the_periods() {
+        // A short read, then periods until the last one is read past the chunk.
+        let mut recorder = fake_recorder(&[Read::Frames(10)], &[]);
+        let data = recorder.read_chunk(1000).unwrap();
+
+        assert_eq!(data.len(), CHANNELS);
+        for (channel, channel_data) in data.iter().enumerate() {
+            assert_eq!(channel_data.channel_num, channel + 1);
+            let expected: Vec<i16> = (0..1000).map(|frame| sample(frame, channel)).collect();
+            assert_eq!(channel_data.channel_data, expected);
+            assert_eq!(channel_data.record_end.unwrap() - channel_data.record_start.unwrap(), crate::sound_card::frames_duration(999, 48000));
+            assert_eq!(channel_data.dropped_frames, 0);
+        }
+        assert_eq!(recorder.alsa_pcm.device.borrow().frame, 10 + 11 * PERIOD as i64);
+    }
+
+    #[test]
+    fn read_chunk_recovers_from_overruns() {
+        let mut recorder = fake_recorder(&[Read::Frames(PERIOD), Read::Overrun(300)], &[]);
+        let data = recorder.read_chunk(4 * PERIOD).unwrap();
+
+        assert_eq!((recorder.xruns, recorder.dropped_frames), (1, 300));
+        assert_eq!(data[0].dropped_frames, 300);
+        assert_eq!(data[1].channel_data.len(), 4 * PERIOD);
+        assert_eq!(data[1].channel_data[PERIOD - 1], sample(PERIOD as i64 - 1, 1));
+        assert_eq!(data[1].channel_data[PERIOD], sample(PERIOD as i64 + 300, 1));
+    }
+
+    #[test]
+    fn record_reconnects_an_unplugged_device() {
+        let mut recorder = fake_recorder(&[Read::Unplugged], &[0]);
+        assert!(matches!(recorder.record(10), Err(crate::sound_card::SoundCardError::DeviceUnplugged { .. })));
+        assert_eq!(recorder.record(10).unwrap()[0].channel_data.len(), 480);
+        assert_eq!(recorder.reconnections, 1);
+    }
+
+    #[test]
+    fn record_loop_waits_for_the_device_to_come_back() {
+        // Unplugged after the first chunk, still missing at the first attempt to reopen it.
+        let mut reads = vec![Read::Frames(PERIOD); 5];
+        reads.push(Read::Unplugged);
+        let mut recorder = fake_recorder(&reads, &[libc::ENOENT, 0]);
+
+        let mut chunks = 0usize;
+        recorder.record_loop(10, &mut |data| {
+            assert_eq!(data[0].channel_data.len(), 480);
+            chunks += 1;
+            chunks < 2
+        }).unwrap();
+        assert_eq!((chunks, recorder.reconnections), (2, 1));
+    }
+
+    #[test]
+    fn record_loop_returns_other_failures_to_reopen() {
+        let mut recorder = fake_recorder(&[Read::Unplugged], &[libc::EACCES]);
+        let result = recorder.record_loop(10, &mut |_| panic!("no chunk can be read"));
+        assert!(matches!(result, Err(crate::sound_card::SoundCardError::PermissionDenied { .. })));
+    }
+}
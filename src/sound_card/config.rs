@@ -3,46 +3,175 @@ pub struct SoundCardConfig {
     pub device_id: String,
     pub format: self::Format,
     pub sampling_rate: self::SamplingRate,
-    pub period_size: usize
+    pub period_size: usize,
+    /// Periods held by the ALSA ring buffer.
+    #[serde(default = "SoundCardConfig::default_buffer_periods")]
+    pub buffer_periods: usize
 }
 
 impl SoundCardConfig {
+    pub const DEFAULT_BUFFER_PERIODS: usize = 8;
+
     pub fn new(device_id: &str, format: self::Format, sampling_rate: self::SamplingRate, period_size: usize) -> Self {
         Self {
             device_id: device_id.to_string(),
             format: format,
             sampling_rate: sampling_rate,
-            period_size: period_size
+            period_size: period_size,
+            buffer_periods: Self::DEFAULT_BUFFER_PERIODS
         }
     }
 
+    fn default_buffer_periods() -> usize {
+        Self::DEFAULT_BUFFER_PERIODS
+    }
+
+    /// Frames of the ALSA ring buffer requested from the device.
+    pub fn buffer_size(&self) -> usize {
+        self.period_size * self.buffer_periods
+    }
+
+}
+
+/// Period and ring buffer sizes, in frames, requested from a device and granted by it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BufferSizes {
+    pub requested_period_size: usize,
+    pub period_size: usize,
+    pub requested_buffer_size: usize,
+    pub buffer_size: usize
+}
+
+impl std::fmt::Display for BufferSizes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "period of {} frames ({} requested), buffer of {} frames ({} requested)", self.period_size, self.requested_period_size, self.buffer_size, self.requested_buffer_size)
+    }
 }
 
 
-#[derive(Debug, Clone, Copy, ::serde::Serialize, ::serde::Deserialize)]
+/// Sample format of the capture, named after the ALSA format in [`Format::label`].
+#[derive(Debug, Clone, Copy, PartialEq, ::serde::Serialize, ::serde::Deserialize)]
 pub enum Format {
     B16,
     B24,
     B32,
+    /// 24 bits in the low bytes of 32.
+    B24In32,
+    F32,
+    U16,
 }
 
 impl Format {
-    
+    /// Full scale of the amplitude every format is brought to by [`Format::normalize`], the one
+    /// of `S16_LE`, so the readings and scaling factors of 16-bit setups keep their meaning.
+    pub const COMMON_FULL_SCALE: f64 = i16::MAX as f64;
+
+    /// Every format, from the finest resolution.
+    pub const ALL: [Format; 6] = [Format::B32, Format::B24In32, Format::B24, Format::F32, Format::B16, Format::U16];
+
     pub fn get_bytes(&self) -> usize {
         match self {
-            Format::B16 => 2,
+            Format::B16 | Format::U16 => 2,
             Format::B24 => 3,
-            Format::B32 => 4
+            Format::B32 | Format::B24In32 | Format::F32 => 4
+        }
+    }
+
+    /// Largest sample magnitude the format can hold, from its zero level.
+    pub fn full_scale(&self) -> f64 {
+        match self {
+            Format::B16 | Format::U16 => i16::MAX as f64,
+            Format::B24 | Format::B24In32 => ((1 << 23) - 1) as f64,
+            Format::B32 => i32::MAX as f64,
+            Format::F32 => 1.
+        }
+    }
+
+    /// Sample value of silence: unsigned formats are offset by half their range.
+    pub fn zero_level(&self) -> f64 {
+        match self {
+            Format::U16 => 32768.,
+            _ => 0.
+        }
+    }
+
+    /// Brings a sample of this format to [`Format::COMMON_FULL_SCALE`].
+    pub fn normalize(&self, sample: f64) -> f64 {
+        (sample - self.zero_level()) * Self::COMMON_FULL_SCALE / self.full_scale()
+    }
+
+    /// Inverse of [`Format::normalize`].
+    pub fn denormalize(&self, value: f64) -> f64 {
+        value * self.full_scale() / Self::COMMON_FULL_SCALE + self.zero_level()
+    }
+
+    /// Converts `sample` of this format to `format`, through the common amplitude scale.
+    pub fn convert(&self, sample: f64, format: Format) -> f64 {
+        if *self == format {
+            return sample;
+        }
+        let converted = format.denormalize(self.normalize(sample));
+        if format == Format::F32 { converted } else { converted.round() }
+    }
+
+    /// Decodes the little endian sample at the start of `bytes`, which holds at least
+    /// [`Format::get_bytes`] bytes.
+    pub fn read_sample(&self, bytes: &[u8]) -> f64 {
+        match self {
+            Format::B16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Format::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Format::B24 => (i32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]) << 8 >> 8) as f64,
+            Format::B24In32 => (i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) << 8 >> 8) as f64,
+            Format::B32 => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            Format::F32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64
+        }
+    }
+
+    /// Appends `sample` to `bytes` in little endian, rounded and clipped to the range of the format.
+    pub fn write_sample(&self, sample: f64, bytes: &mut Vec<u8>) {
+        match self {
+            Format::B16 => bytes.extend_from_slice(&(sample.round() as i16).to_le_bytes()),
+            Format::U16 => bytes.extend_from_slice(&(sample.round() as u16).to_le_bytes()),
+            Format::B24 => bytes.extend_from_slice(&(sample.round().clamp(-8388608., 8388607.) as i32).to_le_bytes()[0..3]),
+            Format::B24In32 => bytes.extend_from_slice(&(sample.round().clamp(-8388608., 8388607.) as i32).to_le_bytes()),
+            Format::B32 => bytes.extend_from_slice(&(sample.round() as i32).to_le_bytes()),
+            Format::F32 => bytes.extend_from_slice(&(sample as f32).to_le_bytes())
+        }
+    }
+
+    /// Returns the ALSA name of the format as used in supersid.cfg.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Format::B16 => "S16_LE",
+            Format::B24 => "S24_3LE",
+            Format::B32 => "S32_LE",
+            Format::B24In32 => "S24_LE",
+            Format::F32 => "FLOAT_LE",
+            Format::U16 => "U16_LE"
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label.to_uppercase().as_str() {
+            "S16_LE" => Some(Format::B16),
+            "S24_3LE" => Some(Format::B24),
+            "S32_LE" => Some(Format::B32),
+            "S24_LE" => Some(Format::B24In32),
+            "FLOAT_LE" => Some(Format::F32),
+            "U16_LE" => Some(Format::U16),
+            _ => None
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, ::serde::Serialize, ::serde::Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, ::serde::Serialize, ::serde::Deserialize)]
 pub enum SamplingRate {
     Hz44100,
     Hz48000,
     Hz96000,
-    Hz192000
+    Hz192000,
+    /// Any other rate within `MIN_CUSTOM` and `MAX_CUSTOM` Hz, such as 88200 or 384000.
+    Custom(usize)
 }
 
 impl SamplingRate {
@@ -52,6 +181,21 @@ impl SamplingRate {
     pub const SAMPLING_RATE_96000: usize = 96000;
     pub const SAMPLING_RATE_192000: usize = 192000;
 
+    /// Range of the custom rates, from telephony rates to the fastest audio interfaces.
+    pub const MIN_CUSTOM: usize = 8000;
+    pub const MAX_CUSTOM: usize = 768000;
+
+    /// Rates audio interfaces commonly offer, from the fastest.
+    pub const COMMON: [SamplingRate; 7] = [
+        SamplingRate::Custom(384000),
+        SamplingRate::Hz192000,
+        SamplingRate::Custom(176400),
+        SamplingRate::Hz96000,
+        SamplingRate::Custom(88200),
+        SamplingRate::Hz48000,
+        SamplingRate::Hz44100
+    ];
+
     pub fn sample_value<T: crate::math::Sample>(&self) -> T {    
         T::from_usize(self.value()).unwrap()
     }
@@ -61,16 +205,108 @@ impl SamplingRate {
             Self::Hz48000 => Self::SAMPLING_RATE_48000,
             Self::Hz96000 => Self::SAMPLING_RATE_96000,
             Self::Hz192000 => Self::SAMPLING_RATE_192000,
-            Self::Hz44100 => Self::SAMPLING_RATE_44100
+            Self::Hz44100 => Self::SAMPLING_RATE_44100,
+            Self::Custom(value) => *value
         }
     }
 
-    pub fn label(value: usize) -> Self {
+    /// Returns the matching rate, a `Custom` one for the other values within `MIN_CUSTOM` and
+    /// `MAX_CUSTOM`, or `None`.
+    pub fn from_value(value: usize) -> Option<Self> {
         match value {
-            Self::SAMPLING_RATE_48000 => Self::Hz48000,
-            Self::SAMPLING_RATE_96000 => Self::Hz96000,
-            Self::SAMPLING_RATE_192000 => Self::Hz192000,
-            _ => Self::Hz44100
+            Self::SAMPLING_RATE_44100 => Some(Self::Hz44100),
+            Self::SAMPLING_RATE_48000 => Some(Self::Hz48000),
+            Self::SAMPLING_RATE_96000 => Some(Self::Hz96000),
+            Self::SAMPLING_RATE_192000 => Some(Self::Hz192000),
+            Self::MIN_CUSTOM..=Self::MAX_CUSTOM => Some(Self::Custom(value)),
+            _ => None
         }
     }
-}
\ No newline at end of file
+
+    /// Returns false for a `Custom` rate out of range, which only a hand-written file can hold.
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Self::Custom(value) => (Self::MIN_CUSTOM..=Self::MAX_CUSTOM).contains(value),
+            _ => true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn written(format: Format, sample: f64) -> Vec<u8> {
+        let mut bytes = Vec::<u8>::new();
+        format.write_sample(sample, &mut bytes);
+        bytes
+    }
+
+    /// The extreme, silent and small samples of each format.
+    fn samples(format: Format) -> Vec<f64> {
+        match format {
+            Format::B16 => vec![-32768., -1., 0., 1., 32767.],
+            Format::U16 => vec![0., 32767., 32768., 32769., 65535.],
+            Format::B24 | Format::B24In32 => vec![-8388608., -1., 0., 1., 8388607.],
+            Format::B32 => vec![-2147483648., -1., 0., 1., 2147483647.],
+            Format::F32 => vec![-1., -0.25, 0., 0.5, 1.]
+        }
+    }
+
+    #[test]
+    fn samples_round_trip_through_bytes() {
+        for format in Format::ALL {
+            for sample in samples(format) {
+                let bytes = written(format, sample);
+                assert_eq!(bytes.len(), format.get_bytes(), "{} {}", format.label(), sample);
+                assert_eq!(format.read_sample(&bytes), sample, "{} {}", format.label(), sample);
+                assert_eq!(written(format, format.read_sample(&bytes)), bytes, "{} {}", format.label(), sample);
+            }
+        }
+    }
+
+    #[test]
+    fn negative_24_bit_samples_are_sign_extended() {
+        assert_eq!(Format::B24.read_sample(&[0xff, 0xff, 0xff]), -1.);
+        assert_eq!(Format::B24.read_sample(&[0x00, 0x00, 0x80]), -8388608.);
+        assert_eq!(written(Format::B24, -2.), vec![0xfe, 0xff, 0xff]);
+        // The padding byte of S24_LE is ignored when read and sign extended when written.
+        assert_eq!(Format::B24In32.read_sample(&[0xff, 0xff, 0xff, 0x00]), -1.);
+        assert_eq!(Format::B24In32.read_sample(&[0x00, 0x00, 0x80, 0x7f]), -8388608.);
+        assert_eq!(written(Format::B24In32, -2.), vec![0xfe, 0xff, 0xff, 0xff]);
+    }
+
+    #[test]
+    fn writes_clip_at_full_scale() {
+        for format in Format::ALL.into_iter().filter(|format| *format != Format::F32) {
+            let samples = samples(format);
+            let (lowest, highest) = (samples[0], samples[samples.len() - 1]);
+            assert_eq!(written(format, highest + 1000.), written(format, highest), "{}", format.label());
+            assert_eq!(written(format, lowest - 1000.), written(format, lowest), "{}", format.label());
+            assert_eq!(written(format, highest + 0.4), written(format, highest), "{}", format.label());
+        }
+    }
+
+    #[test]
+    fn normalize_round_trips() {
+        for format in Format::ALL {
+            assert_eq!(format.normalize(format.zero_level()), 0., "{}", format.label());
+            assert_eq!(format.normalize(format.zero_level() + format.full_scale()), Format::COMMON_FULL_SCALE, "{}", format.label());
+            for sample in samples(format) {
+                let round_trip = format.denormalize(format.normalize(sample));
+                assert!((round_trip - sample).abs() <= 1e-9 * format.full_scale(), "{} {} {}", format.label(), sample, round_trip);
+            }
+        }
+    }
+
+    #[test]
+    fn full_scale_converts_to_full_scale() {
+        for from in Format::ALL {
+            for to in Format::ALL {
+                let converted = from.convert(from.zero_level() + from.full_scale(), to);
+                assert_eq!(converted, to.zero_level() + to.full_scale(), "{} to {}", from.label(), to.label());
+                assert_eq!(from.convert(from.zero_level(), to), to.zero_level(), "{} to {}", from.label(), to.label());
+            }
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! Sound card backed by a file, to run the pipeline on recorded samples without hardware.
+
+use super::config::Format;
+
+/// Prefix of the device ids naming a file, as in `file:/tmp/flare.wav`.
+pub const DEVICE_PREFIX: &str = "file:";
+
+/// Reads WAV files, or raw interleaved little endian samples in the configured format, from the
+/// path of `device_id`, and writes samples to it the same way.
+#[derive(Clone)]
+pub struct FileSoundCard<T: crate::math::Sample> {
+    pub config: super::config::SoundCardConfig,
+    pub pacing: super::Pacing,
+    phantom: std::marker::PhantomData<T>
+}
+
+impl<T: crate::math::Sample> FileSoundCard<T> {
+    /// Returns the path of the file, `device_id` without [`DEVICE_PREFIX`].
+    pub fn path(&self) -> std::path::PathBuf {
+        let device_id = self.config.device_id.as_str();
+        std::path::PathBuf::from(device_id.strip_prefix(DEVICE_PREFIX).unwrap_or(device_id))
+    }
+
+    /// Returns true when the path names a WAV file rather than raw samples.
+    pub fn is_wav(&self) -> bool {
+        self.path().extension().and_then(|extension| extension.to_str()).map(|extension| extension.eq_ignore_ascii_case("wav")).unwrap_or(false)
+    }
+
+    pub fn with_pacing(mut self, pacing: super::Pacing) -> Self {
+        self.pacing = pacing;
+        self
+    }
+
+    pub fn create_file_recorder(&self, channels: usize) -> Result<FileRecorder<T>, super::SoundCardError> {
+        FileRecorder::<T>::new(self.clone(), channels)
+    }
+
+    pub fn create_file_player(&self, channels: usize) -> Result<FilePlayer<T>, super::SoundCardError> {
+        FilePlayer::<T>::new(self.clone(), channels)
+    }
+
+    fn get_error(&self, operation: &str, error: std::io::Error) -> super::SoundCardError {
+        let device = self.config.device_id.clone();
+        match error.kind() {
+            std::io::ErrorKind::NotFound => super::SoundCardError::DeviceNotFound { device },
+            std::io::ErrorKind::PermissionDenied => super::SoundCardError::PermissionDenied { device },
+            _ => super::SoundCardError::Driver { device, operation: operation.to_string(), message: error.to_string() }
+        }
+    }
+}
+
+impl<T: crate::math::Sample> super::SoundCard<T> for FileSoundCard<T> {
+    fn new(config: super::config::SoundCardConfig) -> Result<Self, super::SoundCardError> {
+        Ok(Self { config, pacing: super::Pacing::RealTime, phantom: std::marker::PhantomData })
+    }
+
+    fn config(&self) -> super::config::SoundCardConfig {
+        self.config.clone()
+    }
+}
+
+/// Plays the samples of a file back as if they were captured.
+///
+/// The samples of a WAV file in another format than the configured one are converted through
+/// the common amplitude scale of [`Format::normalize`]. The rate and channels must match. The
+/// frames are dated from the recording time of the WAV file when it has one.
+pub struct FileRecorder<T: crate::math::Sample> {
+    sound_card: FileSoundCard<T>,
+    channels: usize,
+    format: Format,
+    reader: std::io::BufReader<std::fs::File>,
+    remaining: u64,
+    pacer: super::Pacer,
+    buffer: Vec<u8>
+}
+
+impl<T: crate::math::Sample> FileRecorder<T> {
+    fn new(sound_card: FileSoundCard<T>, channels: usize) -> Result<Self, super::SoundCardError> {
+        let config = &sound_card.config;
+        let path = sound_card.path();
+        let file = match std::fs::File::open(&path) {
+            Ok(file) => file,
+            Err(error) => return Err(sound_card.get_error("open the file", error))
+        };
+        let length = match file.metadata() {
+            Ok(metadata) => metadata.len(),
+            Err(error) => return Err(sound_card.get_error("read the file", error))
+        };
+        let mut reader = std::io::BufReader::new(file);
+
+        let (format, remaining, recorded) = if sound_card.is_wav() {
+            let header = match super::wav::WavHeader::read(&mut reader) {
+                Ok(header) => header,
+                Err(error) => return Err(sound_card.get_error("read the WAV header", error))
+            };
+            if header.channels != channels {
+                return Err(super::SoundCardError::ChannelMismatch { device: config.device_id.clone(), expected: channels, actual: header.channels });
+            }
+            if header.sampling_rate != config.sampling_rate.value() {
+                return Err(super::SoundCardError::UnsupportedRate { device: config.device_id.clone(), rate: config.sampling_rate.value(), supported: Some((header.sampling_rate, header.sampling_rate)) });
+            }
+            (header.format, std::cmp::min(header.data_length, length.saturating_sub(header.data_offset)), header.recorded)
+        }
+        else {
+            (config.format, length, None)
+        };
+
+        let mut pacer = super::Pacer::new(sound_card.pacing, config.sampling_rate.value());
+        if let Some(recorded) = recorded {
+            pacer = pacer.with_start_time(recorded);
+        }
+        Ok(Self { sound_card, channels, format, reader, remaining, pacer, buffer: Vec::new() })
+    }
+
+    /// Format of the samples in the file.
+    pub fn file_format(&self) -> Format {
+        self.format
+    }
+}
+
+impl<T: crate::math::Sample> super::SoundCardRecorder<T> for FileRecorder<T> {
+    /// Returns the next `milliseconds` of the file, or what is left of it.
+    fn record(&mut self, milliseconds: usize) -> Result<Vec<super::ChannelData<T>>, super::SoundCardError> {
+        use std::io::Read;
+
+        let config = &self.sound_card.config;
+        let frame_bytes = self.format.get_bytes() * self.channels;
+        let frames = std::cmp::min(config.sampling_rate.value() * milliseconds / 1000, (self.remaining / frame_bytes as u64) as usize);
+        if frames == 0 {
+            return Err(super::SoundCardError::EndOfStream { device: config.device_id.clone() });
+        }
+
+        self.buffer.resize(frames * frame_bytes, 0);
+        if let Err(error) = self.reader.read_exact(&mut self.buffer) {
+            return Err(self.sound_card.get_error("read the file", error));
+        }
+        self.remaining -= self.buffer.len() as u64;
+
+        let interleaved: Vec<T> = self.buffer.chunks_exact(self.format.get_bytes())
+            .map(|bytes| T::from_f64(self.format.convert(self.format.read_sample(bytes), config.format)).unwrap_or_default())
+            .collect();
+
+        let mut data: Vec<super::ChannelData<T>> = (0..self.channels).map(|i| super::ChannelData::<T>::new(i + 1, Vec::with_capacity(frames))).collect();
+        super::deinterleave(&interleaved, &mut data);
+
+        let (record_start, record_end) = self.pacer.advance(frames);
+        for channel in data.iter_mut() {
+            channel.record_start = Some(record_start);
+            channel.record_end = Some(record_end);
+        }
+        Ok(data)
+    }
+
+    /// Hands the file over in chunks, and returns once it is exhausted.
+    fn record_loop(&mut self, milliseconds: usize, each: &mut dyn FnMut(Vec<super::ChannelData<T>>) -> bool) -> Result<(), super::SoundCardError> {
+        loop {
+            match self.record(milliseconds) {
+                Ok(data) => if !each(data) { return Ok(()); },
+                Err(super::SoundCardError::EndOfStream { .. }) => return Ok(()),
+                Err(error) => return Err(error)
+            }
+        }
+    }
+}
+
+/// Destination of a [`FilePlayer`].
+enum FileSink {
+    Raw(std::io::BufWriter<std::fs::File>),
+    Wav(super::wav::WavWriter<std::io::BufWriter<std::fs::File>>)
+}
+
+/// Writes the samples played to a WAV file, or as raw interleaved little endian samples in the
+/// configured format.
+pub struct FilePlayer<T: crate::math::Sample> {
+    sound_card: FileSoundCard<T>,
+    channels: usize,
+    sink: FileSink
+}
+
+impl<T: crate::math::Sample> FilePlayer<T> {
+    fn new(sound_card: FileSoundCard<T>, channels: usize) -> Result<Self, super::SoundCardError> {
+        let config = &sound_card.config;
+        let writer = match std::fs::File::create(sound_card.path()) {
+            Ok(file) => std::io::BufWriter::new(file),
+            Err(error) => return Err(sound_card.get_error("create the file", error))
+        };
+        let sink = if sound_card.is_wav() {
+            let header = super::wav::WavHeader::new(config.format, channels, config.sampling_rate.value());
+            match super::wav::WavWriter::new(writer, header) {
+                Ok(wav_writer) => FileSink::Wav(wav_writer),
+                Err(error) => return Err(sound_card.get_error("write the WAV header", error))
+            }
+        }
+        else {
+            FileSink::Raw(writer)
+        };
+        Ok(Self { sound_card, channels, sink })
+    }
+}
+
+impl<T: crate::math::Sample> super::SoundCardPlayer<T> for FilePlayer<T> {
+    fn wait_for_finish(&mut self) -> Result<(), super::SoundCardError> {
+        use std::io::Write;
+
+        let result = match &mut self.sink {
+            FileSink::Raw(writer) => writer.flush(),
+            FileSink::Wav(wav_writer) => wav_writer.finish()
+        };
+        match result {
+            Ok(()) => Ok(()),
+            Err(error) => Err(self.sound_card.get_error("write the file", error))
+        }
+    }
+
+    fn play(&mut self, data: &[super::ChannelData<T>]) -> Result<(), super::SoundCardError> {
+        use std::io::Write;
+
+        let format = self.sound_card.config.format;
+        if data.len() != self.channels {
+            return Err(super::SoundCardError::ChannelMismatch { device: self.sound_card.config.device_id.clone(), expected: self.channels, actual: data.len() });
+        }
+
+        let result = match &mut self.sink {
+            FileSink::Raw(writer) => writer.write_all(&super::interleave_bytes(data, format, format)),
+            FileSink::Wav(wav_writer) => wav_writer.write_frames(data, format)
+        };
+        match result {
+            Ok(()) => Ok(()),
+            Err(error) => Err(self.sound_card.get_error("write the file", error))
+        }
+    }
+}
@@ -1,29 +1,97 @@
 pub mod config;
 pub mod alsa;
+pub mod file;
+pub mod synthetic;
+pub mod wav;
+pub mod self_test;
 
-pub trait SoundCard<T: crate::math::Sample> {
-    fn new(config: config::SoundCardConfig) -> Self;
+use config::{Format, SoundCardConfig};
+
+pub trait SoundCard<T: crate::math::Sample>: Sized {
+    fn new(config: config::SoundCardConfig) -> Result<Self, SoundCardError>;
     fn config(&self) -> config::SoundCardConfig;
 }
 
 pub trait SoundCardPlayer<T: crate::math::Sample> {
-    fn wait_for_finish(&mut self) -> Result<(), std::io::Error>;
-    fn play(&mut self, data: &[ChannelData<T>]) -> Result<(), std::io::Error>;
+    fn wait_for_finish(&mut self) -> Result<(), SoundCardError>;
+    fn play(&mut self, data: &[ChannelData<T>]) -> Result<(), SoundCardError>;
 }
 
 pub trait SoundCardRecorder<T: crate::math::Sample> {
-    fn record(&mut self, milliseconds: usize) -> Result<Vec<ChannelData<T>>, std::io::Error>;
-    fn record_loop(&mut self, milliseconds: usize, each: &mut dyn FnMut(Vec<ChannelData<T>>));
+    fn record(&mut self, milliseconds: usize) -> Result<Vec<ChannelData<T>>, SoundCardError>;
+    /// Records `milliseconds` long chunks and hands them to `each` until it returns false.
+    fn record_loop(&mut self, milliseconds: usize, each: &mut dyn FnMut(Vec<ChannelData<T>>) -> bool) -> Result<(), SoundCardError>;
+}
+
+/// Errors raised by sound cards, with the device they concern.
+#[derive(Debug)]
+pub enum SoundCardError {
+    DeviceNotFound { device: String },
+    PermissionDenied { device: String },
+    DeviceBusy { device: String },
+    UnsupportedFormat { device: String, format: String },
+    /// `supported` is the range of rates the device reports, when it could be read.
+    UnsupportedRate { device: String, rate: usize, supported: Option<(usize, usize)> },
+    UnsupportedChannels { device: String, channels: usize },
+    /// Samples were lost because they were not read (or written) in time.
+    Xrun { device: String },
+    Suspended { device: String },
+    DeviceUnplugged { device: String },
+    ChannelMismatch { device: String, expected: usize, actual: usize },
+    /// A recorder reading from a file has no samples left.
+    EndOfStream { device: String },
+    /// Any other failure of `operation`, with the message of the driver.
+    Driver { device: String, operation: String, message: String },
 }
 
+impl SoundCardError {
+    /// Classifies the error ALSA returned for `operation` on `device` by its errno.
+    pub fn from_alsa(device: &str, operation: &str, error: ::alsa::Error) -> Self {
+        let device = device.to_string();
+        match error.errno() as libc::c_int {
+            libc::ENOENT | libc::ENXIO => Self::DeviceNotFound { device },
+            libc::EACCES | libc::EPERM => Self::PermissionDenied { device },
+            libc::EBUSY => Self::DeviceBusy { device },
+            libc::EPIPE => Self::Xrun { device },
+            libc::ESTRPIPE => Self::Suspended { device },
+            libc::ENODEV => Self::DeviceUnplugged { device },
+            _ => Self::Driver { device, operation: operation.to_string(), message: error.to_string() }
+        }
+    }
+}
+
+impl std::fmt::Display for SoundCardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DeviceNotFound { device } => write!(f, "Sound card '{}' not found", device),
+            Self::PermissionDenied { device } => write!(f, "Permission denied on sound card '{}', is the user in the 'audio' group?", device),
+            Self::DeviceBusy { device } => write!(f, "Sound card '{}' is used by another program", device),
+            Self::UnsupportedFormat { device, format } => write!(f, "Sound card '{}' does not support the {} format", device, format),
+            Self::UnsupportedRate { device, rate, supported: Some((min, max)) } => write!(f, "Sound card '{}' does not support {} Hz, only rates within {} and {} Hz", device, rate, min, max),
+            Self::UnsupportedRate { device, rate, supported: None } => write!(f, "Sound card '{}' does not support {} Hz", device, rate),
+            Self::UnsupportedChannels { device, channels } => write!(f, "Sound card '{}' does not support {} channel(s)", device, channels),
+            Self::Xrun { device } => write!(f, "Sound card '{}' overran, samples were lost", device),
+            Self::Suspended { device } => write!(f, "Sound card '{}' was suspended", device),
+            Self::DeviceUnplugged { device } => write!(f, "Sound card '{}' was disconnected", device),
+            Self::ChannelMismatch { device, expected, actual } => write!(f, "Sound card '{}' was given {} channel(s) instead of {}", device, actual, expected),
+            Self::EndOfStream { device } => write!(f, "Sound card '{}' has no samples left", device),
+            Self::Driver { device, operation, message } => write!(f, "Could not {} on sound card '{}': {}", operation, device, message)
+        }
+    }
+}
 
+impl std::error::Error for SoundCardError {}
 
 #[derive(Debug, Clone)]
 pub struct ChannelData<T: crate::math::Sample> {
     pub channel_num: usize,
     pub channel_data: Vec<T>,
-    pub record_start: std::time::Instant,
-    pub record_end: Option<std::time::Instant>
+    /// UTC time the first frame was captured at, when the sound card reports it.
+    pub record_start: Option<chrono::DateTime<chrono::Utc>>,
+    /// UTC time the last frame was captured at, when the sound card reports it.
+    pub record_end: Option<chrono::DateTime<chrono::Utc>>,
+    /// Frames lost to overruns between `record_start` and `record_end`.
+    pub dropped_frames: usize
 }
 
 impl<T: crate::math::Sample> ChannelData<T> {
@@ -31,8 +99,227 @@ impl<T: crate::math::Sample> ChannelData<T> {
         Self {
             channel_num: channel_num,
             channel_data: channel_data,
-            record_start: std::time::Instant::now(),
-            record_end: None
+            record_start: None,
+            record_end: None,
+            dropped_frames: 0
+        }
+    }
+}
+
+/// Appends the frames of `interleaved` to the channels of `data`, one sample per channel and
+/// frame. A trailing incomplete frame is left out of every channel, so they keep the same length.
+pub fn deinterleave<T: crate::math::Sample>(interleaved: &[T], data: &mut [ChannelData<T>]) {
+    let channels = data.len();
+    if channels == 0 {
+        return;
+    }
+
+    let mut i: usize = 0;
+    let mut j: usize;
+    while i < interleaved.len() / channels {
+        j = 0;
+        while j < channels {
+            data[j].channel_data.push(interleaved[i * channels + j]);
+            j += 1;
+        }
+        i += 1;
+    }
+}
+
+/// Returns the time `frames` frames take at `sampling_rate`.
+pub fn frames_duration(frames: i64, sampling_rate: usize) -> chrono::Duration {
+    chrono::Duration::microseconds(frames * 1_000_000 / sampling_rate as i64)
+}
+
+/// How recorders that do not capture from hardware deliver their samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pacing {
+    /// Each chunk is returned once it would have been captured, as a sound card does.
+    RealTime,
+    /// Chunks are returned right away, to process a file faster than it was recorded.
+    AsFastAsPossible
+}
+
+/// Dates the chunks of a recorder that does not capture from hardware, from the time it was
+/// created, and holds them back with [`Pacing::RealTime`].
+#[derive(Debug, Clone)]
+pub struct Pacer {
+    pacing: Pacing,
+    sampling_rate: usize,
+    started: std::time::Instant,
+    start_time: chrono::DateTime<chrono::Utc>,
+    frames: usize
+}
+
+impl Pacer {
+    pub fn new(pacing: Pacing, sampling_rate: usize) -> Self {
+        Self { pacing, sampling_rate, started: std::time::Instant::now(), start_time: chrono::Utc::now(), frames: 0 }
+    }
+
+    /// Dates the first frame at `start_time` instead of the creation of the pacer.
+    pub fn with_start_time(mut self, start_time: chrono::DateTime<chrono::Utc>) -> Self {
+        self.start_time = start_time;
+        self
+    }
+
+    /// Accounts for the next `frames` frames and returns the times of the first and last one.
+    pub fn advance(&mut self, frames: usize) -> (chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>) {
+        let first = self.start_time + frames_duration(self.frames as i64, self.sampling_rate);
+        let last = self.start_time + frames_duration((self.frames + frames.max(1) - 1) as i64, self.sampling_rate);
+        self.frames += frames;
+
+        if self.pacing == Pacing::RealTime {
+            let due = self.started + std::time::Duration::from_micros((self.frames as u64) * 1_000_000 / self.sampling_rate as u64);
+            let now = std::time::Instant::now();
+            if due > now {
+                std::thread::sleep(due - now);
+            }
+        }
+        (first, last)
+    }
+}
+
+/// Returns the frames of `data` interleaved as little endian samples of `stored`, converted from
+/// the samples of `format`. Frames missing from some channels are left out.
+pub fn interleave_bytes<T: crate::math::Sample>(data: &[ChannelData<T>], format: config::Format, stored: config::Format) -> Vec<u8> {
+    let frames = data.iter().map(|channel| channel.channel_data.len()).min().unwrap_or(0);
+    let mut bytes = Vec::<u8>::with_capacity(frames * data.len() * stored.get_bytes());
+    let mut i = 0usize;
+    while i < frames {
+        for channel in data.iter() {
+            stored.write_sample(format.convert(channel.channel_data[i].to_f64().unwrap(), stored), &mut bytes);
         }
+        i += 1;
     }
-}
\ No newline at end of file
+    bytes
+}
+
+/// Returns true when the device id names an ALSA PCM rather than a file or synthetic tones.
+pub fn is_alsa_device(device_id: &str) -> bool {
+    !device_id.starts_with(file::DEVICE_PREFIX) && !device_id.starts_with(synthetic::DEVICE_PREFIX)
+}
+
+/// Records with the sample type matching the configured format and converts the samples to f64
+/// on the common amplitude scale of [`Format::normalize`]. File and synthetic devices are read
+/// with `pacing`.
+pub fn record_f64(config: &SoundCardConfig, channels: usize, milliseconds: usize, pacing: Pacing) -> Result<Vec<ChannelData<f64>>, SoundCardError> {
+    match config.format {
+        Format::B16 => record_as::<i16>(config, channels, milliseconds, pacing),
+        Format::B24 => record_as::<crate::math::i24>(config, channels, milliseconds, pacing),
+        Format::B32 => record_as::<i32>(config, channels, milliseconds, pacing),
+        Format::B24In32 => record_as::<crate::math::i24in32>(config, channels, milliseconds, pacing),
+        Format::F32 => record_as::<f32>(config, channels, milliseconds, pacing),
+        Format::U16 => record_as::<u16>(config, channels, milliseconds, pacing)
+    }
+}
+
+/// Creates the recorder of the device id: a file, a synthetic sound card with `pacing`, or an
+/// ALSA PCM. Also returns how it captures, for the user.
+pub fn create_recorder<T: crate::math::Sample + ::alsa::pcm::IoFormat + 'static>(config: &SoundCardConfig, channels: usize, pacing: Pacing) -> Result<(Box<dyn SoundCardRecorder<T>>, String), SoundCardError> {
+    if config.device_id.starts_with(file::DEVICE_PREFIX) {
+        let sound_card = file::FileSoundCard::<T>::new(config.clone())?.with_pacing(pacing);
+        let recorder = sound_card.create_file_recorder(channels)?;
+        let description = format!("reading {} samples", recorder.file_format().label());
+        return Ok((Box::new(recorder), description));
+    }
+    if config.device_id.starts_with(synthetic::DEVICE_PREFIX) {
+        let sound_card = synthetic::SyntheticSoundCard::<T>::new(config.clone())?.with_pacing(pacing);
+        let description = format!("generating {} tone(s) in noise", sound_card.tones.len());
+        return Ok((Box::new(sound_card.create_synthetic_recorder(channels)?), description));
+    }
+
+    let sound_card = alsa::AlsaSoundCard::<T>::new(config.clone())?;
+    let recorder = sound_card.create_alsa_recorder(channels)?;
+    let description = format!("with a {}", recorder.buffer_sizes);
+    Ok((Box::new(recorder), description))
+}
+
+fn record_as<T: crate::math::Sample + ::alsa::pcm::IoFormat + 'static>(config: &SoundCardConfig, channels: usize, milliseconds: usize, pacing: Pacing) -> Result<Vec<ChannelData<f64>>, SoundCardError> {
+    let (mut recorder, _) = create_recorder::<T>(config, channels, pacing)?;
+    let data = recorder.record(milliseconds)?;
+
+    Ok(data.into_iter().map(|channel| ChannelData::<f64> {
+        channel_num: channel.channel_num,
+        channel_data: channel.channel_data.iter().map(|sample| config.format.normalize(sample.to_f64().unwrap())).collect(),
+        record_start: channel.record_start,
+        record_end: channel.record_end,
+        dropped_frames: channel.dropped_frames
+    }).collect())
+}
+
+/// Plays `seconds` of a tone of `frequency` Hz and `amplitude` relative to full scale on every
+/// channel. A file device receives the raw samples, to check the tone without a sound card.
+pub fn play_tone<T: crate::math::Sample + ::alsa::pcm::IoFormat>(config: &SoundCardConfig, channels: usize, frequency: f64, amplitude: f64, seconds: usize) -> Result<(), SoundCardError> {
+    let sampling_rate = config.sampling_rate.value();
+    let samples: Vec<T> = crate::math::generate_tone::<f64>(frequency, sampling_rate as f64, sampling_rate, amplitude * Format::COMMON_FULL_SCALE)
+        .into_iter()
+        .map(|value| T::from_f64(config.format.denormalize(value).round()).unwrap())
+        .collect();
+    let one_second: Vec<ChannelData<T>> = (0..channels).map(|i| ChannelData::<T>::new(i + 1, samples.clone())).collect();
+
+    let mut player: Box<dyn SoundCardPlayer<T>> = if config.device_id.starts_with(file::DEVICE_PREFIX) {
+        Box::new(file::FileSoundCard::<T>::new(config.clone())?.create_file_player(channels)?)
+    }
+    else {
+        Box::new(alsa::AlsaSoundCard::<T>::new(config.clone())?.create_alsa_player(channels)?)
+    };
+    let mut i = 0usize;
+    while i < seconds {
+        player.play(&one_second)?;
+        i += 1;
+    }
+    player.wait_for_finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channels(count: usize) -> Vec<ChannelData<i16>> {
+        (0..count).map(|i| ChannelData::<i16>::new(i + 1, Vec::new())).collect()
+    }
+
+    fn samples(data: &[ChannelData<i16>]) -> Vec<Vec<i16>> {
+        data.iter().map(|channel| channel.channel_data.clone()).collect()
+    }
+
+    #[test]
+    fn deinterleaves_one_two_and_four_channels() {
+        let interleaved: Vec<i16> = (0..8).collect();
+
+        let mut data = channels(1);
+        deinterleave(&interleaved, &mut data);
+        assert_eq!(samples(&data), vec![interleaved.clone()]);
+
+        let mut data = channels(2);
+        deinterleave(&interleaved, &mut data);
+        assert_eq!(samples(&data), vec![vec![0, 2, 4, 6], vec![1, 3, 5, 7]]);
+
+        let mut data = channels(4);
+        deinterleave(&interleaved, &mut data);
+        assert_eq!(samples(&data), vec![vec![0, 4], vec![1, 5], vec![2, 6], vec![3, 7]]);
+    }
+
+    #[test]
+    fn short_reads_append_to_the_channels() {
+        let mut data = channels(2);
+        deinterleave(&[0, 1, 2, 3], &mut data);
+        deinterleave(&[4, 5], &mut data);
+        deinterleave(&[], &mut data);
+        assert_eq!(samples(&data), vec![vec![0, 2, 4], vec![1, 3, 5]]);
+    }
+
+    #[test]
+    fn trailing_partial_frame_is_dropped() {
+        let mut data = channels(4);
+        deinterleave(&[0, 1, 2, 3, 4, 5, 6], &mut data);
+        assert_eq!(samples(&data), vec![vec![0], vec![1], vec![2], vec![3]]);
+
+        // Fewer samples than channels: no channel gets any.
+        let mut data = channels(4);
+        deinterleave(&[0, 1, 2], &mut data);
+        assert!(data.iter().all(|channel| channel.channel_data.is_empty()));
+
+        deinterleave(&[0, 1, 2], &mut []);
+    }
+}
@@ -0,0 +1,265 @@
+//! Recording of one capture configuration while a test tone plays, and the checks of
+//! `find_alsa_devices.py` that tell whether the configuration works.
+
+use super::config::{Format, SoundCardConfig};
+use super::ChannelData;
+
+/// Amplitude of the test tone relative to full scale.
+const TONE_AMPLITUDE: f64 = 0.5;
+/// Time given to the playback to start before recording.
+const TONE_LEAD: std::time::Duration = std::time::Duration::from_millis(200);
+/// Accepted deviation of the recording time, 10 %.
+const DURATION_TOLERANCE: f64 = 0.1;
+
+/// Outcome of recording one configuration.
+#[derive(Debug, Clone)]
+pub struct SelfTestResult {
+    pub config: SoundCardConfig,
+    pub channels: usize,
+    /// Frequency of the test tone in Hz, unknown when none is played nor given.
+    pub tone: Option<f64>,
+    /// Time taken by the recording, device opening included.
+    pub elapsed: std::time::Duration,
+    /// Peak frequency of each channel in Hz.
+    pub peaks: Vec<f64>,
+    /// Why the configuration does not work.
+    pub error: Option<String>
+}
+
+impl SelfTestResult {
+    pub fn new(config: SoundCardConfig, channels: usize, tone: Option<f64>) -> Self {
+        Self { config, channels, tone, elapsed: std::time::Duration::ZERO, peaks: Vec::new(), error: None }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Checks `milliseconds` recorded in `elapsed`, and sets the peaks of the channels and the
+    /// first problem found.
+    pub fn check(&mut self, data: &[ChannelData<f64>], milliseconds: usize) {
+        let frames = self.config.sampling_rate.value() * milliseconds / 1000;
+        if data.len() != self.channels || data.iter().any(|channel| channel.channel_data.len() < frames) {
+            self.error = Some(format!("fewer than the {} frames expected", frames));
+            return;
+        }
+        let dropped_frames: usize = data.iter().map(|channel| channel.dropped_frames).max().unwrap_or(0);
+        if dropped_frames > 0 {
+            self.error = Some(format!("overrun, {} frames dropped", dropped_frames));
+            return;
+        }
+        if let Some(channel) = data.iter().find(|channel| channel.channel_data.iter().all(|sample| *sample == channel.channel_data[0])) {
+            self.error = Some(format!("channel {} recorded a constant", channel.channel_num));
+            return;
+        }
+        if self.channels > 1 && data.iter().all(|channel| channel.channel_data == data[0].channel_data) {
+            self.error = Some("the channels are identical, the device is mono".to_string());
+            return;
+        }
+        let expected = milliseconds as f64 / 1000.;
+        if (self.elapsed.as_secs_f64() - expected).abs() > expected * DURATION_TOLERANCE {
+            self.error = Some(format!("took {} ms instead of {} ms", self.elapsed.as_millis(), milliseconds));
+            return;
+        }
+
+        let sampling_rate = self.config.sampling_rate.sample_value::<f64>();
+        let n = crate::supersid::get_N(sampling_rate);
+        let spec_density: Vec<crate::spectral_density::SpectralDensity<f64>> = data.iter().map(|channel| crate::spectral_density::SpectralDensity::<f64>::new(&channel.channel_data, sampling_rate, n)).collect();
+        self.peaks = spec_density.iter().filter_map(|sd| sd.peak.map(|peak| peak.frequency())).collect();
+        if let Some(tone) = self.tone {
+            // The peak must fall in the bin of the tone or a neighbouring one.
+            if let Some(sd) = spec_density.iter().find(|sd| sd.peak.map(|peak| (peak.frequency() - tone).abs() > 1.5 * sd.freq_step).unwrap_or(true)) {
+                self.error = Some(format!("the peak is not at the {} Hz of the tone, bins of {} Hz", tone, sd.freq_step));
+            }
+        }
+    }
+}
+
+/// Records `config` while `tone_device` plays a third of the rate, or while an external
+/// generator feeds `external` Hz, and checks the recording.
+pub fn test_configuration(config: SoundCardConfig, channels: usize, milliseconds: usize, tone_device: Option<String>, external: Option<f64>) -> SelfTestResult {
+    let tone = match (&tone_device, external) {
+        (_, Some(frequency)) => Some(frequency),
+        (Some(_), None) => Some((config.sampling_rate.value() / 3) as f64),
+        (None, None) => None
+    };
+    let mut result = SelfTestResult::new(config.clone(), channels, tone);
+
+    let player = match (tone_device, tone) {
+        (Some(device), Some(frequency)) => {
+            let mut tone_config = config.clone();
+            tone_config.device_id = device;
+            tone_config.format = Format::B16;
+            let seconds = milliseconds.div_ceil(1000) + 1;
+            let player = std::thread::spawn(move || super::play_tone::<i16>(&tone_config, channels, frequency, TONE_AMPLITUDE, seconds));
+            std::thread::sleep(TONE_LEAD);
+            Some(player)
+        },
+        _ => None
+    };
+
+    let started = std::time::Instant::now();
+    let recorded = super::record_f64(&config, channels, milliseconds, super::Pacing::RealTime);
+    result.elapsed = started.elapsed();
+    let played = match player {
+        Some(player) => match player.join() {
+            Ok(played) => played,
+            Err(_) => Err(super::SoundCardError::Driver { device: config.device_id.clone(), operation: "play the test tone".to_string(), message: "the playback thread panicked".to_string() })
+        },
+        None => Ok(())
+    };
+
+    let data = match recorded {
+        Ok(data) => data,
+        Err(error) => {
+            result.error = Some(error.to_string());
+            return result;
+        }
+    };
+    if let Err(error) = played {
+        result.error = Some(format!("no test tone: {}", error));
+        return result;
+    }
+
+    result.check(&data, milliseconds);
+    result
+}
+
+/// Returns the working configuration to recommend: the highest rate, then the finest format,
+/// then the most channels.
+pub fn recommend(results: &[SelfTestResult]) -> Option<&SelfTestResult> {
+    results.iter()
+        .filter(|result| result.is_ok())
+        .min_by_key(|result| (std::cmp::Reverse(result.config.sampling_rate.value()), Format::ALL.iter().position(|format| *format == result.config.format), std::cmp::Reverse(result.channels)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sound_card::config::SamplingRate;
+
+    const MILLISECONDS: usize = 1000;
+    const TONE: f64 = 16000.;
+
+    fn config(sampling_rate: SamplingRate, format: Format) -> SoundCardConfig {
+        SoundCardConfig::new("hw:0,0", format, sampling_rate, 1024)
+    }
+
+    /// Returns a result for a second recorded on time at 48 kHz, expecting `TONE`.
+    fn result(channels: usize) -> SelfTestResult {
+        let mut result = SelfTestResult::new(config(SamplingRate::Hz48000, Format::B16), channels, Some(TONE));
+        result.elapsed = std::time::Duration::from_millis(MILLISECONDS as u64);
+        result
+    }
+
+    /// Returns `channels` channels of a second of `frequency` Hz in noise that differs on each.
+    fn recording(channels: usize, frequency: f64) -> Vec<ChannelData<f64>> {
+        (0..channels).map(|i| ChannelData::<f64>::new(i + 1, crate::math::generate_tone_with_noise::<f64>(frequency, 48000., 48000, 0.5 * Format::COMMON_FULL_SCALE))).collect()
+    }
+
+    #[test]
+    fn tone_at_the_peak_works() {
+        let mut result = result(2);
+        result.check(&recording(2, TONE), MILLISECONDS);
+
+        assert_eq!(result.error, None);
+        assert_eq!(result.peaks.len(), 2);
+        // The bins are about 1 kHz wide at 48 kHz.
+        for peak in result.peaks.iter() {
+            assert!((peak - TONE).abs() < 1000., "peak at {} Hz", peak);
+        }
+    }
+
+    #[test]
+    fn tone_away_from_the_peak_fails() {
+        let mut result = result(1);
+        result.check(&recording(1, 5000.), MILLISECONDS);
+
+        assert!(result.error.unwrap().starts_with("the peak is not at the 16000 Hz of the tone"));
+        assert!((result.peaks[0] - 5000.).abs() < 1000.);
+    }
+
+    #[test]
+    fn no_tone_checks_the_samples_only() {
+        let mut result = result(1);
+        result.tone = None;
+        result.check(&recording(1, 5000.), MILLISECONDS);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn short_recordings_fail() {
+        let mut data = recording(2, TONE);
+        data[1].channel_data.truncate(47000);
+        let mut result = result(2);
+        result.check(&data, MILLISECONDS);
+        assert_eq!(result.error.as_deref(), Some("fewer than the 48000 frames expected"));
+
+        let mut result = self::result(2);
+        result.check(&recording(1, TONE), MILLISECONDS);
+        assert_eq!(result.error.as_deref(), Some("fewer than the 48000 frames expected"));
+    }
+
+    #[test]
+    fn dropped_frames_fail() {
+        let mut data = recording(2, TONE);
+        data[0].dropped_frames = 480;
+        let mut result = result(2);
+        result.check(&data, MILLISECONDS);
+        assert_eq!(result.error.as_deref(), Some("overrun, 480 frames dropped"));
+    }
+
+    #[test]
+    fn constant_channels_fail() {
+        let mut data = recording(2, TONE);
+        data[1].channel_data = vec![0.; 48000];
+        let mut result = result(2);
+        result.check(&data, MILLISECONDS);
+        assert_eq!(result.error.as_deref(), Some("channel 2 recorded a constant"));
+    }
+
+    #[test]
+    fn identical_channels_fail() {
+        let mut data = recording(2, TONE);
+        data[1].channel_data = data[0].channel_data.clone();
+        let mut result = result(2);
+        result.check(&data, MILLISECONDS);
+        assert_eq!(result.error.as_deref(), Some("the channels are identical, the device is mono"));
+    }
+
+    #[test]
+    fn late_recordings_fail() {
+        let mut result = result(1);
+        result.elapsed = std::time::Duration::from_millis(1200);
+        result.check(&recording(1, TONE), MILLISECONDS);
+        assert_eq!(result.error.as_deref(), Some("took 1200 ms instead of 1000 ms"));
+
+        let mut result = self::result(1);
+        result.elapsed = std::time::Duration::from_millis(1050);
+        result.check(&recording(1, TONE), MILLISECONDS);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn recommends_the_highest_rate_then_the_finest_format_then_the_most_channels() {
+        let working = |sampling_rate: SamplingRate, format: Format, channels: usize| SelfTestResult::new(config(sampling_rate, format), channels, None);
+        let mut failed = working(SamplingRate::Hz192000, Format::B32, 2);
+        failed.error = Some("overrun, 1 frames dropped".to_string());
+
+        let mut results = vec![
+            failed,
+            working(SamplingRate::Hz48000, Format::B32, 2),
+            working(SamplingRate::Hz96000, Format::B16, 1),
+            working(SamplingRate::Hz96000, Format::B16, 2)
+        ];
+        let best = recommend(&results).unwrap();
+        assert_eq!((best.config.sampling_rate, best.config.format, best.channels), (SamplingRate::Hz96000, Format::B16, 2));
+
+        results.push(working(SamplingRate::Hz96000, Format::B32, 1));
+        let best = recommend(&results).unwrap();
+        assert_eq!((best.config.format, best.channels), (Format::B32, 1));
+
+        results.retain(|result| !result.is_ok());
+        assert!(recommend(&results).is_none());
+    }
+}
@@ -0,0 +1,138 @@
+//! Sound card generating VLF tones in noise, to run the pipeline without hardware.
+
+use super::config::Format;
+
+/// Prefix of the device ids of synthetic sound cards, followed by the tones as
+/// `<frequency>[@<amplitude>]` separated by commas, as in `synthetic:19800@0.2,24000`.
+pub const DEVICE_PREFIX: &str = "synthetic:";
+
+/// Amplitude of the tones given without one, relative to full scale.
+pub const DEFAULT_AMPLITUDE: f64 = 0.1;
+
+/// A tone added to every channel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    pub frequency: f64,
+    /// Amplitude relative to full scale, 0 to 1.
+    pub amplitude: f64
+}
+
+/// Generates the tones of `device_id` plus the white noise of
+/// [`crate::math::generate_tone_with_noise`] on every channel.
+#[derive(Clone)]
+pub struct SyntheticSoundCard<T: crate::math::Sample> {
+    pub config: super::config::SoundCardConfig,
+    pub tones: Vec<Tone>,
+    pub pacing: super::Pacing,
+    phantom: std::marker::PhantomData<T>
+}
+
+impl<T: crate::math::Sample> SyntheticSoundCard<T> {
+    /// Parses the tones following [`DEVICE_PREFIX`] in `device_id`. No tone leaves only noise.
+    pub fn parse_tones(device_id: &str) -> Result<Vec<Tone>, String> {
+        let tones = device_id.strip_prefix(DEVICE_PREFIX).unwrap_or(device_id);
+        let mut parsed = Vec::<Tone>::new();
+        for tone in tones.split(',').map(|tone| tone.trim()).filter(|tone| !tone.is_empty()) {
+            let (frequency, amplitude) = match tone.split_once('@') {
+                Some((frequency, amplitude)) => (frequency, Some(amplitude)),
+                None => (tone, None)
+            };
+            let frequency = match frequency.trim().parse::<f64>() {
+                Ok(frequency) if frequency > 0. => frequency,
+                _ => return Err(format!("'{}' is not a frequency in Hz", frequency))
+            };
+            let amplitude = match amplitude.map(|amplitude| amplitude.trim().parse::<f64>()) {
+                None => DEFAULT_AMPLITUDE,
+                Some(Ok(amplitude)) if (0f64..=1f64).contains(&amplitude) => amplitude,
+                Some(_) => return Err(format!("the amplitude of '{}' must be within 0 and 1", tone))
+            };
+            parsed.push(Tone { frequency, amplitude });
+        }
+        Ok(parsed)
+    }
+
+    pub fn with_pacing(mut self, pacing: super::Pacing) -> Self {
+        self.pacing = pacing;
+        self
+    }
+
+    pub fn create_synthetic_recorder(&self, channels: usize) -> Result<SyntheticRecorder<T>, super::SoundCardError> {
+        if channels < 1 {
+            return Err(super::SoundCardError::UnsupportedChannels { device: self.config.device_id.clone(), channels });
+        }
+        let pacer = super::Pacer::new(self.pacing, self.config.sampling_rate.value());
+        Ok(SyntheticRecorder { sound_card: self.clone(), channels, pacer })
+    }
+}
+
+impl<T: crate::math::Sample> super::SoundCard<T> for SyntheticSoundCard<T> {
+    fn new(config: super::config::SoundCardConfig) -> Result<Self, super::SoundCardError> {
+        let tones = match Self::parse_tones(&config.device_id) {
+            Ok(tones) => tones,
+            Err(message) => return Err(super::SoundCardError::Driver { device: config.device_id.clone(), operation: "parse the tones".to_string(), message })
+        };
+        let nyquist = config.sampling_rate.value() as f64 / 2.;
+        if let Some(tone) = tones.iter().find(|tone| tone.frequency >= nyquist) {
+            return Err(super::SoundCardError::Driver { device: config.device_id.clone(), operation: "parse the tones".to_string(), message: format!("{} Hz is above the Nyquist frequency of {} Hz", tone.frequency, nyquist) });
+        }
+        Ok(Self { config, tones, pacing: super::Pacing::RealTime, phantom: std::marker::PhantomData })
+    }
+
+    fn config(&self) -> super::config::SoundCardConfig {
+        self.config.clone()
+    }
+}
+
+/// Records the tones of its [`SyntheticSoundCard`]. Every chunk starts at phase zero.
+pub struct SyntheticRecorder<T: crate::math::Sample> {
+    sound_card: SyntheticSoundCard<T>,
+    channels: usize,
+    pacer: super::Pacer
+}
+
+impl<T: crate::math::Sample> SyntheticRecorder<T> {
+    /// Returns `frames` samples of the tones and noise, in the configured format.
+    fn generate(&self, frames: usize) -> Vec<T> {
+        let format = self.sound_card.config.format;
+        let sampling_rate = self.sound_card.config.sampling_rate.value() as f64;
+        let (first, others) = match self.sound_card.tones.split_first() {
+            Some((first, others)) => (*first, others),
+            None => (Tone { frequency: 0., amplitude: 0. }, &[][..])
+        };
+
+        let mut samples = crate::math::generate_tone_with_noise::<f64>(first.frequency, sampling_rate, frames, first.amplitude * Format::COMMON_FULL_SCALE);
+        for tone in others.iter() {
+            let tone_samples = crate::math::generate_tone::<f64>(tone.frequency, sampling_rate, frames, tone.amplitude * Format::COMMON_FULL_SCALE);
+            for (sample, tone_sample) in samples.iter_mut().zip(tone_samples.iter()) {
+                *sample += tone_sample;
+            }
+        }
+        samples.iter()
+            .map(|sample| format.denormalize(sample.clamp(-Format::COMMON_FULL_SCALE, Format::COMMON_FULL_SCALE)).round())
+            .map(|sample| T::from_f64(sample).unwrap_or_default())
+            .collect()
+    }
+}
+
+impl<T: crate::math::Sample> super::SoundCardRecorder<T> for SyntheticRecorder<T> {
+    fn record(&mut self, milliseconds: usize) -> Result<Vec<super::ChannelData<T>>, super::SoundCardError> {
+        let frames = self.sound_card.config.sampling_rate.value() * milliseconds / 1000;
+        let mut data: Vec<super::ChannelData<T>> = (0..self.channels).map(|i| super::ChannelData::<T>::new(i + 1, self.generate(frames))).collect();
+
+        let (record_start, record_end) = self.pacer.advance(frames);
+        for channel in data.iter_mut() {
+            channel.record_start = Some(record_start);
+            channel.record_end = Some(record_end);
+        }
+        Ok(data)
+    }
+
+    fn record_loop(&mut self, milliseconds: usize, each: &mut dyn FnMut(Vec<super::ChannelData<T>>) -> bool) -> Result<(), super::SoundCardError> {
+        loop {
+            let data = self.record(milliseconds)?;
+            if !each(data) {
+                return Ok(());
+            }
+        }
+    }
+}
@@ -0,0 +1,314 @@
+//! RIFF WAVE files holding interleaved samples of one of the [`Format`]s, to archive captures
+//! and replay them through [`super::file::FileSoundCard`].
+
+use super::config::Format;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+/// Bytes of the sub format GUID following its format tag.
+const KSDATAFORMAT_SUBTYPE_SUFFIX: [u8; 14] = [0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71];
+/// Format of the time of the first frame in the ICRD entry.
+const RECORDED_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.6fZ";
+
+/// Layout of the samples of a WAV file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WavHeader {
+    pub format: Format,
+    pub channels: usize,
+    pub sampling_rate: usize,
+    /// UTC time of the first frame, kept in the ICRD entry of the LIST INFO chunk.
+    pub recorded: Option<chrono::DateTime<chrono::Utc>>,
+    /// Offset of the first sample from the start of the file.
+    pub data_offset: u64,
+    /// Length of the samples in bytes.
+    pub data_length: u64
+}
+
+impl WavHeader {
+    /// Returns the header of a file to write samples of `format` to. WAV has no unsigned 16-bit
+    /// samples, [`Format::U16`] is stored as [`Format::B16`].
+    pub fn new(format: Format, channels: usize, sampling_rate: usize) -> Self {
+        let format = if format == Format::U16 { Format::B16 } else { format };
+        Self { format, channels, sampling_rate, recorded: None, data_offset: 0, data_length: 0 }
+    }
+
+    /// Reads the chunks of `reader` up to the start of the samples, where it is left.
+    pub fn read<R: std::io::Read>(reader: &mut R) -> Result<Self, std::io::Error> {
+        use std::io::Read;
+
+        let mut riff = [0u8; 12];
+        reader.read_exact(&mut riff)?;
+        if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+            return Err(invalid("not a RIFF WAVE file"));
+        }
+
+        let mut offset = riff.len() as u64;
+        let mut layout: Option<(Format, usize, usize)> = None;
+        let mut recorded: Option<chrono::DateTime<chrono::Utc>> = None;
+        loop {
+            let mut chunk = [0u8; 8];
+            reader.read_exact(&mut chunk)?;
+            offset += chunk.len() as u64;
+            let id = [chunk[0], chunk[1], chunk[2], chunk[3]];
+            let length = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as u64;
+
+            if &id == b"data" {
+                let (format, channels, sampling_rate) = match layout {
+                    Some(layout) => layout,
+                    None => return Err(invalid("data chunk before the fmt chunk"))
+                };
+                return Ok(Self { format, channels, sampling_rate, recorded, data_offset: offset, data_length: length });
+            }
+
+            // Chunks are padded to an even length.
+            let padded = length + length % 2;
+            let mut body = Vec::<u8>::new();
+            reader.by_ref().take(padded).read_to_end(&mut body)?;
+            if (body.len() as u64) < padded {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
+            }
+            offset += padded;
+            if &id == b"fmt " {
+                layout = Some(Self::parse_fmt(&body)?);
+            }
+            else if &id == b"LIST" {
+                recorded = Self::parse_recorded(&body).or(recorded);
+            }
+        }
+    }
+
+    /// Returns the format, channels and sampling rate of a fmt chunk.
+    fn parse_fmt(body: &[u8]) -> Result<(Format, usize, usize), std::io::Error> {
+        if body.len() < 16 {
+            return Err(invalid("fmt chunk too short"));
+        }
+        let mut tag = u16::from_le_bytes([body[0], body[1]]);
+        let channels = u16::from_le_bytes([body[2], body[3]]) as usize;
+        let sampling_rate = u32::from_le_bytes([body[4], body[5], body[6], body[7]]) as usize;
+        let block_align = u16::from_le_bytes([body[12], body[13]]) as usize;
+        let container_bits = u16::from_le_bytes([body[14], body[15]]);
+        let mut valid_bits = container_bits;
+        if tag == WAVE_FORMAT_EXTENSIBLE {
+            if body.len() < 40 {
+                return Err(invalid("extensible fmt chunk too short"));
+            }
+            valid_bits = u16::from_le_bytes([body[18], body[19]]);
+            // The sub format GUID starts with the format tag it extends.
+            tag = u16::from_le_bytes([body[24], body[25]]);
+        }
+        if channels == 0 || block_align != channels * (container_bits as usize).div_ceil(8) {
+            return Err(invalid("inconsistent fmt chunk"));
+        }
+
+        let format = match (tag, container_bits, valid_bits) {
+            (WAVE_FORMAT_PCM, 16, 16) => Format::B16,
+            (WAVE_FORMAT_PCM, 24, 24) => Format::B24,
+            (WAVE_FORMAT_PCM, 32, 24) => Format::B24In32,
+            (WAVE_FORMAT_PCM, 32, 32) => Format::B32,
+            (WAVE_FORMAT_IEEE_FLOAT, 32, 32) => Format::F32,
+            _ => return Err(invalid(&format!("unsupported sample format {} with {} bits", tag, valid_bits)))
+        };
+        Ok((format, channels, sampling_rate))
+    }
+
+    /// Returns the time of the ICRD entry of a LIST INFO chunk, when it is one of ours.
+    fn parse_recorded(body: &[u8]) -> Option<chrono::DateTime<chrono::Utc>> {
+        if body.len() < 4 || &body[0..4] != b"INFO" {
+            return None;
+        }
+        let mut i = 4usize;
+        while i + 8 <= body.len() {
+            let length = u32::from_le_bytes([body[i + 4], body[i + 5], body[i + 6], body[i + 7]]) as usize;
+            let end = std::cmp::min(i + 8 + length, body.len());
+            if &body[i..i + 4] == b"ICRD" {
+                let text = String::from_utf8_lossy(&body[i + 8..end]);
+                return chrono::NaiveDateTime::parse_from_str(text.trim_end_matches('\0'), RECORDED_FORMAT).ok().map(|time| time.and_utc());
+            }
+            i = end + (end - i) % 2;
+        }
+        None
+    }
+
+    /// Returns the fmt chunk, extensible for 24 bits in 32 so readers know the padding.
+    fn fmt_chunk(&self) -> Vec<u8> {
+        let container_bits = (self.format.get_bytes() * 8) as u16;
+        let block_align = (self.channels * self.format.get_bytes()) as u16;
+        let tag = if self.format == Format::F32 { WAVE_FORMAT_IEEE_FLOAT } else { WAVE_FORMAT_PCM };
+        let extensible = self.format == Format::B24In32;
+
+        let mut body = Vec::<u8>::new();
+        body.extend_from_slice(&(if extensible { WAVE_FORMAT_EXTENSIBLE } else { tag }).to_le_bytes());
+        body.extend_from_slice(&(self.channels as u16).to_le_bytes());
+        body.extend_from_slice(&(self.sampling_rate as u32).to_le_bytes());
+        body.extend_from_slice(&(self.sampling_rate as u32 * block_align as u32).to_le_bytes());
+        body.extend_from_slice(&block_align.to_le_bytes());
+        body.extend_from_slice(&container_bits.to_le_bytes());
+        if extensible {
+            body.extend_from_slice(&22u16.to_le_bytes());
+            body.extend_from_slice(&24u16.to_le_bytes());
+            body.extend_from_slice(&0u32.to_le_bytes());
+            body.extend_from_slice(&tag.to_le_bytes());
+            body.extend_from_slice(&KSDATAFORMAT_SUBTYPE_SUFFIX);
+        }
+        chunk(b"fmt ", &body)
+    }
+
+    /// Writes the chunks up to the start of the samples and sets `data_offset`.
+    pub fn write<W: std::io::Write>(&mut self, writer: &mut W) -> Result<(), std::io::Error> {
+        let mut chunks = self.fmt_chunk();
+        if let Some(recorded) = self.recorded {
+            let mut text = recorded.format(RECORDED_FORMAT).to_string().into_bytes();
+            text.push(0);
+            let mut info = b"INFO".to_vec();
+            info.extend_from_slice(&chunk(b"ICRD", &text));
+            chunks.extend_from_slice(&chunk(b"LIST", &info));
+        }
+
+        let riff_length = 4 + chunks.len() as u64 + 8 + self.data_length + self.data_length % 2;
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(riff_length as u32).to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+        writer.write_all(&chunks)?;
+        writer.write_all(b"data")?;
+        writer.write_all(&(self.data_length as u32).to_le_bytes())?;
+        self.data_offset = 12 + chunks.len() as u64 + 8;
+        Ok(())
+    }
+}
+
+/// Writes the frames handed over after a [`WavHeader`], and updates the lengths in the header
+/// on [`WavWriter::finish`] since they are not known beforehand.
+pub struct WavWriter<W: std::io::Write + std::io::Seek> {
+    writer: W,
+    header: WavHeader
+}
+
+impl<W: std::io::Write + std::io::Seek> WavWriter<W> {
+    pub fn new(mut writer: W, mut header: WavHeader) -> Result<Self, std::io::Error> {
+        header.data_length = 0;
+        header.write(&mut writer)?;
+        Ok(Self { writer, header })
+    }
+
+    /// Appends the frames of `data`, whose samples are in `format`.
+    pub fn write_frames<T: crate::math::Sample>(&mut self, data: &[super::ChannelData<T>], format: Format) -> Result<(), std::io::Error> {
+        if data.len() != self.header.channels {
+            return Err(invalid(&format!("{} channel(s) given to a file of {}", data.len(), self.header.channels)));
+        }
+        let bytes = super::interleave_bytes(data, format, self.header.format);
+        self.writer.write_all(&bytes)?;
+        self.header.data_length += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Writes the lengths of the samples written so far to the header, then flushes. More
+    /// frames can follow.
+    pub fn finish(&mut self) -> Result<(), std::io::Error> {
+        let end = self.header.data_offset + self.header.data_length;
+        if self.header.data_length % 2 == 1 {
+            self.writer.write_all(&[0])?;
+        }
+        self.writer.seek(std::io::SeekFrom::Start(0))?;
+        self.header.write(&mut self.writer)?;
+        self.writer.seek(std::io::SeekFrom::Start(end))?;
+        self.writer.flush()
+    }
+}
+
+/// Writes `data`, whose samples are in `format`, to a WAV file dated by the first frame.
+pub fn save<T: crate::math::Sample>(path: &std::path::Path, data: &[super::ChannelData<T>], format: Format, sampling_rate: usize) -> Result<(), std::io::Error> {
+    let mut header = WavHeader::new(format, data.len(), sampling_rate);
+    header.recorded = data.first().and_then(|channel| channel.record_start);
+    let mut writer = WavWriter::new(std::io::BufWriter::new(std::fs::File::create(path)?), header)?;
+    writer.write_frames(data, format)?;
+    writer.finish()
+}
+
+/// Returns a chunk of `body`, padded to an even length.
+fn chunk(id: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut chunk = id.to_vec();
+    chunk.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    chunk.extend_from_slice(body);
+    if body.len() % 2 == 1 {
+        chunk.push(0);
+    }
+    chunk
+}
+
+fn invalid(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Three channels of five frames around silence and at both full scales of `format`.
+    fn frames(format: Format) -> Vec<crate::sound_card::ChannelData<f64>> {
+        let zero = format.zero_level();
+        let (lowest, highest, step) = match format {
+            Format::F32 => (-1., 1., 0.5),
+            Format::U16 => (0., 65535., 1.),
+            _ => (-format.full_scale() - 1., format.full_scale(), 1.)
+        };
+        let mut samples = vec![lowest, zero - step, zero, zero + step, highest];
+        (0..3).map(|channel| {
+            samples.rotate_left(1);
+            let mut data = crate::sound_card::ChannelData::<f64>::new(channel + 1, samples.clone());
+            data.record_start = Some(chrono::DateTime::from_timestamp(1_792_000_000, 123_456_000).unwrap());
+            data
+        }).collect()
+    }
+
+    fn file_recorder(path: &std::path::Path, format: Format, sampling_rate: crate::sound_card::config::SamplingRate, channels: usize) -> Result<crate::sound_card::file::FileRecorder<f64>, crate::sound_card::SoundCardError> {
+        use crate::sound_card::SoundCard;
+
+        let config = crate::sound_card::config::SoundCardConfig::new(&format!("{}{}", crate::sound_card::file::DEVICE_PREFIX, path.display()), format, sampling_rate, 1024);
+        crate::sound_card::file::FileSoundCard::<f64>::new(config).unwrap().with_pacing(crate::sound_card::Pacing::AsFastAsPossible).create_file_recorder(channels)
+    }
+
+    #[test]
+    fn saved_files_replay_through_the_file_recorder() {
+        use crate::sound_card::SoundCardRecorder;
+
+        let directory = std::env::temp_dir().join(format!("supersid-wav-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        for format in [Format::B16, Format::B24, Format::B24In32, Format::B32, Format::F32, Format::U16] {
+            let path = directory.join(format!("{}.wav", format.label()));
+            let data = frames(format);
+            save(&path, &data, format, 96000).unwrap();
+
+            let header = WavHeader::read(&mut std::io::BufReader::new(std::fs::File::open(&path).unwrap())).unwrap();
+            assert_eq!(header.format, if format == Format::U16 { Format::B16 } else { format });
+            assert_eq!((header.channels, header.sampling_rate), (3, 96000));
+            assert_eq!(header.recorded, data[0].record_start);
+            assert_eq!(header.data_length, (3 * 5 * header.format.get_bytes()) as u64);
+            assert_eq!(std::fs::metadata(&path).unwrap().len() % 2, 0, "{}", format.label());
+
+            let mut recorder = file_recorder(&path, format, crate::sound_card::config::SamplingRate::Hz96000, 3).unwrap();
+            let replayed = recorder.record(1000).unwrap();
+            for (replayed, data) in replayed.iter().zip(data.iter()) {
+                assert_eq!(replayed.channel_num, data.channel_num);
+                assert_eq!(replayed.channel_data, data.channel_data, "{}", format.label());
+                assert_eq!(replayed.record_start, data.record_start);
+                assert_eq!(replayed.record_end, Some(data.record_start.unwrap() + crate::sound_card::frames_duration(4, 96000)));
+            }
+            assert!(matches!(recorder.record(1000), Err(crate::sound_card::SoundCardError::EndOfStream { .. })));
+        }
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn file_recorder_checks_the_layout_of_the_file() {
+        let path = std::env::temp_dir().join(format!("supersid-wav-layout-{}.wav", std::process::id()));
+        save(&path, &frames(Format::B16), Format::B16, 96000).unwrap();
+
+        let channels = file_recorder(&path, Format::B16, crate::sound_card::config::SamplingRate::Hz96000, 2);
+        assert!(matches!(channels, Err(crate::sound_card::SoundCardError::ChannelMismatch { expected: 2, actual: 3, .. })));
+        let rate = file_recorder(&path, Format::B16, crate::sound_card::config::SamplingRate::Hz48000, 3);
+        assert!(matches!(rate, Err(crate::sound_card::SoundCardError::UnsupportedRate { rate: 48000, .. })));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -14,7 +14,11 @@ pub struct SpectralDensity<T: Measurement> {
     pub N: usize,
     pub seems_off: bool,
     pub all_match: bool,
-    pub data: std::collections::LinkedList<SpectralDensitySample<T, T>>
+    pub data: std::collections::LinkedList<SpectralDensitySample<T, T>>,
+    /// UTC time of the first frame the spectral density was computed from, when known.
+    pub record_start: Option<chrono::DateTime<chrono::Utc>>,
+    /// UTC time of the last frame the spectral density was computed from, when known.
+    pub record_end: Option<chrono::DateTime<chrono::Utc>>
 }
 
 impl<T: Measurement> SpectralDensity<T> {
@@ -82,10 +86,19 @@ impl<T: Measurement> SpectralDensity<T> {
             N: N,
             data: spectral_densities,
             all_match: all_match,
-            seems_off: seems_off
+            seems_off: seems_off,
+            record_start: None,
+            record_end: None
          }
     
     }
+
+    /// Sets the UTC times of the first and last frame, usually those of the recorded channel.
+    pub fn with_record_times(mut self, record_start: Option<chrono::DateTime<chrono::Utc>>, record_end: Option<chrono::DateTime<chrono::Utc>>) -> Self {
+        self.record_start = record_start;
+        self.record_end = record_end;
+        self
+    }
     
 
     /// Returns [Welch] [Builder] given the `signal` sampled at `fs`Hz
@@ -0,0 +1,345 @@
+//! Frequency response of a sound card, measured by `supersid calibrate` through a loopback cable,
+//! and the correction of the station readings it implies.
+
+use super::config::ConfigError;
+use crate::sound_card::config::{Format, SoundCardConfig};
+
+/// Attenuation in dB below which the anti-alias filter is considered to roll off.
+pub const ROLL_OFF_DB: f64 = -3.;
+
+/// Highest boost applied to a reading, so that frequencies the card barely passes do not blow
+/// the noise up.
+pub const MAX_CORRECTION_DB: f64 = 20.;
+
+/// Bins on each side of a tone added to its power, to take in the leakage of the window.
+const TONE_BINS: usize = 2;
+
+/// Lowest ratio of a tone to the noise floor for its point to count.
+const MIN_SNR_DB: f64 = 10.;
+
+/// Gain of the capture chain at one frequency.
+#[derive(Debug, Clone, Copy, PartialEq, ::serde::Serialize, ::serde::Deserialize)]
+pub struct CalibrationPoint {
+    pub frequency: f64,
+    /// Power measured relative to the reference frequency, in dB.
+    pub gain_db: f64
+}
+
+/// Correction curve of one device at one sampling rate.
+#[derive(Debug, Clone, ::serde::Serialize, ::serde::Deserialize)]
+pub struct Calibration {
+    pub device_id: String,
+    pub sampling_rate: usize,
+    /// Lowest measured frequency, which the gains are relative to.
+    pub reference_frequency: f64,
+    /// Power measured at the reference frequency relative to the power played, in dB. It
+    /// includes the playback and mixer levels, so it only tells whether the loopback was loud.
+    pub loopback_gain_db: f64,
+    /// First frequency attenuated by more than [`ROLL_OFF_DB`], if any was reached.
+    pub roll_off: Option<f64>,
+    /// Measured points by increasing frequency.
+    pub points: Vec<CalibrationPoint>
+}
+
+impl Calibration {
+    /// Builds the curve from the power measured at each frequency, `None` where the tone did
+    /// not rise above the noise. The first frequency with a power is the reference.
+    pub fn from_measurements(device_id: &str, sampling_rate: usize, played_power: f64, measurements: &[(f64, Option<f64>)]) -> Option<Self> {
+        let (reference_frequency, reference_power) = measurements.iter().find_map(|(frequency, power)| power.map(|power| (*frequency, power)))?;
+
+        let points: Vec<CalibrationPoint> = measurements.iter()
+            .filter_map(|(frequency, power)| power.map(|power| CalibrationPoint { frequency: *frequency, gain_db: 10. * (power / reference_power).log10() }))
+            .collect();
+
+        let mut roll_off: Option<f64> = None;
+        let mut i = 1usize;
+        while i < points.len() && roll_off.is_none() {
+            let (previous, point) = (points[i - 1], points[i]);
+            if point.gain_db < ROLL_OFF_DB {
+                // Interpolated between the last point above and the first one below.
+                let ratio = (ROLL_OFF_DB - previous.gain_db) / (point.gain_db - previous.gain_db);
+                roll_off = Some(previous.frequency + ratio * (point.frequency - previous.frequency));
+            }
+            i += 1;
+        }
+
+        Some(Self {
+            device_id: device_id.to_string(),
+            sampling_rate,
+            reference_frequency,
+            loopback_gain_db: 10. * (reference_power / played_power).log10(),
+            roll_off,
+            points
+        })
+    }
+
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> Result<Self, ConfigError> {
+        let contents = std::fs::read_to_string(path)?;
+        match toml::from_str::<Self>(&contents) {
+            Ok(calibration) if calibration.points.is_empty() => Err(ConfigError::Parse("the calibration has no point".to_string())),
+            Ok(calibration) => Ok(calibration),
+            Err(error) => Err(ConfigError::Parse(error.to_string()))
+        }
+    }
+
+    pub fn save<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), ConfigError> {
+        let contents = match toml::to_string_pretty(self) {
+            Ok(contents) => contents,
+            Err(error) => return Err(ConfigError::Serialize(error.to_string()))
+        };
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// Loads the calibration file of `config`, if it names one, and checks that it was measured
+    /// on the configured device and sampling rate.
+    pub fn for_config(config: &super::config::SuperSidConfig) -> Result<Option<Self>, ConfigError> {
+        let path = match &config.calibration {
+            Some(path) => path,
+            None => return Ok(None)
+        };
+        let calibration = Self::load(path)?;
+        if calibration.device_id != config.sound_card.device_id || calibration.sampling_rate != config.sound_card.sampling_rate.value() {
+            return Err(ConfigError::Validation {
+                field: "calibration".to_string(),
+                reason: format!("'{}' was measured on '{}' at {} Hz, not on '{}' at {} Hz", path.display(), calibration.device_id, calibration.sampling_rate, config.sound_card.device_id, config.sound_card.sampling_rate.value())
+            });
+        }
+        Ok(Some(calibration))
+    }
+
+    /// Returns the gain at `frequency`, interpolated between the measured points and held
+    /// constant beyond them.
+    pub fn gain_db(&self, frequency: f64) -> f64 {
+        let (first, last) = match (self.points.first(), self.points.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return 0.
+        };
+        if frequency <= first.frequency {
+            return first.gain_db;
+        }
+        if frequency >= last.frequency {
+            return last.gain_db;
+        }
+
+        let i = self.points.iter().position(|point| point.frequency >= frequency).unwrap_or(self.points.len() - 1);
+        let (previous, point) = (self.points[i - 1], self.points[i]);
+        let ratio = (frequency - previous.frequency) / (point.frequency - previous.frequency);
+        previous.gain_db + ratio * (point.gain_db - previous.gain_db)
+    }
+
+    /// Divides the power of a reading by the gain at its frequency, boosting it by at most
+    /// [`MAX_CORRECTION_DB`].
+    pub fn correct<T: crate::spectral_density::Measurement>(&self, sample: crate::spectral_density::SpectralDensitySample<T, T>) -> crate::spectral_density::SpectralDensitySample<T, T> {
+        let correction_db = (-self.gain_db(sample.frequency().to_f64().unwrap_or(0.))).min(MAX_CORRECTION_DB);
+        let factor = T::from_f64(10f64.powf(correction_db / 10.)).unwrap();
+        crate::spectral_density::SpectralDensitySample::new(sample.frequency(), sample.spectral_density() * factor)
+    }
+}
+
+impl std::fmt::Display for Calibration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let lowest = self.points.iter().map(|point| point.gain_db).fold(f64::INFINITY, f64::min);
+        write!(f, "{} points from {} to {} Hz, gain down to {:.1} dB", self.points.len(), self.reference_frequency, self.points.last().map(|point| point.frequency).unwrap_or(0.), lowest)?;
+        match self.roll_off {
+            Some(roll_off) => write!(f, ", rolls off from {:.0} Hz", roll_off),
+            None => write!(f, ", no roll-off measured")
+        }
+    }
+}
+
+/// Returns the power of a tone of `amplitude` relative to full scale, on the common amplitude
+/// scale of [`Format::normalize`].
+pub fn played_power(amplitude: f64) -> f64 {
+    (amplitude * Format::COMMON_FULL_SCALE).powi(2) / 2.
+}
+
+/// Returns the power of the tone of `frequency` Hz in `samples`, `None` when it does not rise
+/// above the noise floor.
+pub fn tone_power(samples: &[f64], sampling_rate: usize, frequency: usize) -> Option<f64> {
+    let n = super::get_N(sampling_rate as f64);
+    // Fewer samples than one segment give no spectrum.
+    if samples.len() < n {
+        return None;
+    }
+    let sd = crate::spectral_density::SpectralDensity::<f64>::new(samples, sampling_rate as f64, n);
+    let bins: Vec<f64> = sd.data.iter().map(|sample| sample.spectral_density()).collect();
+    let bin = super::config::StationConfig::new("", 'k', frequency).get_bin(sd.freq_step);
+    // A tone above the Nyquist frequency.
+    if bin >= bins.len() {
+        return None;
+    }
+    let around = &bins[bin.saturating_sub(TONE_BINS)..=std::cmp::min(bin + TONE_BINS, bins.len() - 1)];
+    let peak = around.iter().cloned().fold(0f64, f64::max);
+
+    // The median of the other bins, which other tones and the leakage of this one barely move.
+    let mut noise: Vec<f64> = bins.iter().enumerate().filter(|(i, _)| i.abs_diff(bin) > TONE_BINS).map(|(_, density)| *density).collect();
+    noise.sort_by(f64::total_cmp);
+    let noise_floor = *noise.get(noise.len() / 2)?;
+    if 10. * (peak / noise_floor).log10() < MIN_SNR_DB {
+        return None;
+    }
+    // The density is two-sided, half of the power of the tone lies in the positive bins.
+    Some(2. * around.iter().sum::<f64>() * sd.freq_step)
+}
+
+/// Plays each of `frequencies` through `playback`, linked to the ALSA capture device of
+/// `config`, and returns the power of each tone measured on `channel`. `each` receives the
+/// measurements as they are taken.
+pub fn measure(config: &SoundCardConfig, playback: &str, channels: usize, channel: usize, frequencies: &[usize], amplitude: f64, each: &mut dyn FnMut(usize, Option<f64>)) -> Result<Vec<(f64, Option<f64>)>, crate::sound_card::SoundCardError> {
+    match config.format {
+        Format::B16 => measure_as::<i16>(config, playback, channels, channel, frequencies, amplitude, each),
+        Format::B24 => measure_as::<crate::math::i24>(config, playback, channels, channel, frequencies, amplitude, each),
+        Format::B32 => measure_as::<i32>(config, playback, channels, channel, frequencies, amplitude, each),
+        Format::B24In32 => measure_as::<crate::math::i24in32>(config, playback, channels, channel, frequencies, amplitude, each),
+        Format::F32 => measure_as::<f32>(config, playback, channels, channel, frequencies, amplitude, each),
+        Format::U16 => measure_as::<u16>(config, playback, channels, channel, frequencies, amplitude, each)
+    }
+}
+
+fn measure_as<T: crate::math::Sample + ::alsa::pcm::IoFormat>(config: &SoundCardConfig, playback: &str, channels: usize, channel: usize, frequencies: &[usize], amplitude: f64, each: &mut dyn FnMut(usize, Option<f64>)) -> Result<Vec<(f64, Option<f64>)>, crate::sound_card::SoundCardError> {
+    use crate::sound_card::SoundCard;
+    use crate::sound_card::alsa::AlsaSoundCardLink;
+
+    let mut playback_config = config.clone();
+    playback_config.device_id = playback.to_string();
+    playback_config.format = Format::B16;
+    let mut recorder = crate::sound_card::alsa::AlsaSoundCard::<T>::new(config.clone())?.create_alsa_recorder(channels)?;
+    let mut player = crate::sound_card::alsa::AlsaSoundCard::<i16>::new(playback_config)?.create_alsa_player(channels)?;
+    recorder.link(&mut player)?;
+
+    let sampling_rate = config.sampling_rate.value();
+    // One second is recorded after the playback buffer is filled.
+    let frames = sampling_rate + player.buffer_sizes.buffer_size;
+
+    let mut measurements = Vec::<(f64, Option<f64>)>::with_capacity(frequencies.len());
+    for frequency in frequencies.iter() {
+        let samples: Vec<i16> = crate::math::generate_tone::<f64>(*frequency as f64, sampling_rate as f64, frames, amplitude * Format::COMMON_FULL_SCALE)
+            .into_iter()
+            .map(|value| value.round() as i16)
+            .collect();
+        let tone: Vec<crate::sound_card::ChannelData<i16>> = (0..channels).map(|i| crate::sound_card::ChannelData::<i16>::new(i + 1, samples.clone())).collect();
+        let recorded = recorder.record_while_playing(&mut player, &tone)?;
+
+        let scaled: Vec<f64> = recorded[channel].channel_data.iter().map(|sample| config.format.normalize(sample.to_f64().unwrap())).collect();
+        let power = tone_power(&scaled, sampling_rate, *frequency);
+        each(*frequency, power);
+        measurements.push((*frequency as f64, power));
+    }
+    Ok(measurements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spectral_density::SpectralDensitySample;
+
+    const POWER: f64 = 1e6;
+
+    /// Flat up to 10 kHz, then down 6 dB at 20 kHz; 1 kHz did not come through.
+    fn calibration() -> Calibration {
+        let measurements = [(1000., None), (2000., Some(POWER)), (10000., Some(POWER)), (20000., Some(POWER / 4.))];
+        Calibration::from_measurements("hw:0,0", 48000, 2. * POWER, &measurements).unwrap()
+    }
+
+    fn assert_close(value: f64, expected: f64) {
+        assert!((value - expected).abs() < 1e-6, "{} instead of {}", value, expected);
+    }
+
+    #[test]
+    fn gains_are_relative_to_the_first_tone_that_came_through() {
+        let calibration = calibration();
+
+        assert_eq!(calibration.reference_frequency, 2000.);
+        assert_close(calibration.loopback_gain_db, -10. * 2f64.log10());
+        let frequencies: Vec<f64> = calibration.points.iter().map(|point| point.frequency).collect();
+        assert_eq!(frequencies, vec![2000., 10000., 20000.]);
+        assert_close(calibration.points[1].gain_db, 0.);
+        assert_close(calibration.points[2].gain_db, -10. * 4f64.log10());
+    }
+
+    #[test]
+    fn roll_off_is_interpolated_at_3_db() {
+        let roll_off = calibration().roll_off.unwrap();
+        assert_close(roll_off, 10000. + 10000. * ROLL_OFF_DB / (-10. * 4f64.log10()));
+
+        let flat = Calibration::from_measurements("hw:0,0", 48000, POWER, &[(1000., Some(POWER)), (20000., Some(POWER * 0.9))]).unwrap();
+        assert_eq!(flat.roll_off, None);
+    }
+
+    #[test]
+    fn no_tone_gives_no_calibration() {
+        assert!(Calibration::from_measurements("hw:0,0", 48000, POWER, &[(1000., None), (2000., None)]).is_none());
+        assert!(Calibration::from_measurements("hw:0,0", 48000, POWER, &[]).is_none());
+    }
+
+    #[test]
+    fn gain_is_interpolated_and_held_beyond_the_points() {
+        let calibration = calibration();
+        let lowest = -10. * 4f64.log10();
+
+        assert_close(calibration.gain_db(15000.), lowest / 2.);
+        assert_close(calibration.gain_db(5000.), 0.);
+        assert_close(calibration.gain_db(100.), 0.);
+        assert_close(calibration.gain_db(24000.), lowest);
+
+        let empty = Calibration { points: Vec::new(), ..calibration };
+        assert_eq!(empty.gain_db(15000.), 0.);
+    }
+
+    #[test]
+    fn correction_undoes_the_gain_up_to_the_limit() {
+        let mut calibration = calibration();
+        let corrected = calibration.correct(SpectralDensitySample::<f64, f64>::new(20000., 1.));
+        assert_close(corrected.frequency(), 20000.);
+        assert_close(corrected.spectral_density(), 4.);
+        assert_close(calibration.correct(SpectralDensitySample::<f64, f64>::new(5000., 1.)).spectral_density(), 1.);
+
+        calibration.points[2].gain_db = -30.;
+        assert_close(calibration.correct(SpectralDensitySample::<f64, f64>::new(20000., 1.)).spectral_density(), 10f64.powf(MAX_CORRECTION_DB / 10.));
+
+        calibration.points[2].gain_db = 10. * 2f64.log10();
+        assert_close(calibration.correct(SpectralDensitySample::<f64, f64>::new(20000., 1.)).spectral_density(), 0.5);
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let path = std::env::temp_dir().join(format!("supersid_calibration_{}.toml", std::process::id()));
+        let calibration = calibration();
+        calibration.save(&path).unwrap();
+        let loaded = Calibration::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.device_id, calibration.device_id);
+        assert_eq!(loaded.sampling_rate, calibration.sampling_rate);
+        assert_eq!(loaded.roll_off, calibration.roll_off);
+        assert_eq!(loaded.points, calibration.points);
+    }
+
+    #[test]
+    fn tone_power_measures_the_played_power() {
+        let samples = crate::math::generate_tone_with_noise::<f64>(5000., 48000., 48000, 0.5 * Format::COMMON_FULL_SCALE);
+        let power = tone_power(&samples, 48000, 5000).unwrap();
+        let gain_db = 10. * (power / played_power(0.5)).log10();
+        assert!(gain_db.abs() < 0.5, "measured {} dB of the played power", gain_db);
+    }
+
+    #[test]
+    fn tone_power_ignores_the_noise() {
+        let samples = crate::math::generate_tone_with_noise::<f64>(5000., 48000., 48000, 0.);
+        assert_eq!(tone_power(&samples, 48000, 5000), None);
+
+        // A tone elsewhere is not the tone measured.
+        let samples = crate::math::generate_tone_with_noise::<f64>(15000., 48000., 48000, 0.5 * Format::COMMON_FULL_SCALE);
+        assert_eq!(tone_power(&samples, 48000, 5000), None);
+    }
+
+    #[test]
+    fn tone_power_outside_the_spectrum_is_none() {
+        assert_eq!(tone_power(&[], 48000, 5000), None);
+        assert_eq!(tone_power(&[0.; 100], 48000, 5000), None);
+
+        let samples = crate::math::generate_tone_with_noise::<f64>(5000., 48000., 48000, 0.5 * Format::COMMON_FULL_SCALE);
+        assert_eq!(tone_power(&samples, 48000, 30000), None);
+    }
+}
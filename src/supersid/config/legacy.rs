@@ -0,0 +1,514 @@
+//! Loader for the `supersid.cfg` INI files used by the Python SuperSID monitor.
+//!
+//! The file is made of `[PARAMETERS]`, `[STATION_n]`, `[Capture]`, `[FTP]` and `[Email]`
+//! sections. Keys are case insensitive, section names are not. Every key the Python monitor
+//! understood is accepted; keys that only matter to the Python viewers and plotting scripts
+//! (`viewer`, `psd_min`, the `[Email]` section, ...) are read and ignored.
+
+use super::{ConfigError, FtpConfig, LogFormat, LogType, SampleIntegrationAlgorithm, StationChannel, StationConfig, SuperSidConfig, SuperSidSite};
+use crate::sound_card::config::{Format, SamplingRate, SoundCardConfig};
+
+pub const PARAMETERS_SECTION: &str = "PARAMETERS";
+pub const CAPTURE_SECTION: &str = "Capture";
+pub const FTP_SECTION: &str = "FTP";
+pub const EMAIL_SECTION: &str = "Email";
+pub const LINUX_SECTION: &str = "Linux";
+pub const STATION_SECTION_PREFIX: &str = "STATION_";
+
+pub const DEFAULT_DATA_PATH: &str = "../Data/";
+pub const DEFAULT_LOCAL_TMP: &str = "../outgoing";
+pub const DEFAULT_DEVICE: &str = "plughw:CARD=Generic,DEV=0";
+pub const DEFAULT_PERIOD_SIZE: usize = 1024;
+pub const DEFAULT_BEMA_WING: usize = 6;
+
+// Keys the Python monitor accepts but which have no meaning for this crate.
+const IGNORED_PARAMETERS: [&str; 6] = ["viewer", "mode", "psd_min", "psd_max", "psd_ticks", "paper_size"];
+const IGNORED_CAPTURE: [&str; 2] = ["audio", "card"];
+const IGNORED_LINUX: [&str; 3] = ["audio", "card", "periodsize"];
+const IGNORED_EMAIL: [&str; 6] = ["from_mail", "email_server", "email_port", "email_tls", "email_login", "email_password"];
+
+impl SuperSidConfig {
+    /// Reads a legacy `supersid.cfg` file.
+    ///
+    /// Relative `data_path` and `local_tmp` values are resolved against the directory of the
+    /// file, which matches the layout the Python monitor expects (`Config/` next to `Data/`).
+    pub fn from_legacy_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, ConfigError> {
+        let contents = std::fs::read_to_string(path.as_ref())?;
+        let mut config = Self::from_legacy_str(&contents)?;
+        config.resolve_paths(path.as_ref());
+        Ok(config)
+    }
+
+    /// Parses the contents of a legacy `supersid.cfg` file.
+    pub fn from_legacy_str(contents: &str) -> Result<Self, ConfigError> {
+        let mut ini = LegacyIni::parse(contents)?;
+
+        let mut parameters = match ini.take_section(PARAMETERS_SECTION) {
+            Some(section) => section,
+            None => return Err(ConfigError::MissingSection { section: PARAMETERS_SECTION.to_string() })
+        };
+        let mut capture = ini.take_section(CAPTURE_SECTION).unwrap_or_else(|| LegacySection::empty(CAPTURE_SECTION));
+        let mut ftp = ini.take_section(FTP_SECTION).unwrap_or_else(|| LegacySection::empty(FTP_SECTION));
+
+        for key in IGNORED_PARAMETERS.iter() {
+            parameters.take(key);
+        }
+        for key in IGNORED_CAPTURE.iter() {
+            capture.take(key);
+        }
+        if let Some(mut linux) = ini.take_section(LINUX_SECTION) {
+            for key in IGNORED_LINUX.iter() {
+                linux.take(key);
+            }
+            linux.ensure_consumed()?;
+        }
+        if let Some(mut email) = ini.take_section(EMAIL_SECTION) {
+            for key in IGNORED_EMAIL.iter() {
+                email.take(key);
+            }
+            email.ensure_consumed()?;
+        }
+
+        let site = SuperSidSite {
+            site_name: parameters.take_required("site_name")?.value,
+            site_contact_email: parameters.take_required("contact")?.value,
+            site_latitude: parameters.take_required("latitude")?.parse::<f64>(PARAMETERS_SECTION, "expected a decimal latitude")?,
+            site_longitude: parameters.take_required("longitude")?.parse::<f64>(PARAMETERS_SECTION, "expected a decimal longitude")?,
+            utc_offset: parameters.take_required("utc_offset")?.value,
+            time_zone: parameters.take_required("time_zone")?.value,
+        };
+
+        let monitor_id = parameters.take_required("monitor_id")?.value;
+
+        let log_type_entry = parameters.take_required("log_type")?;
+        let log_type = match LogType::from_label(&log_type_entry.value) {
+            Some(log_type) => log_type,
+            None => return Err(log_type_entry.invalid(PARAMETERS_SECTION, "must be either 'filtered' or 'raw'"))
+        };
+
+        let rate_entry = parameters.take_required("audio_sampling_rate")?;
+        let sampling_rate = match SamplingRate::from_value(rate_entry.parse::<usize>(PARAMETERS_SECTION, "expected a rate in Hz")?) {
+            Some(sampling_rate) => sampling_rate,
+            None => return Err(rate_entry.invalid(PARAMETERS_SECTION, &format!("must be within {} and {} Hz", SamplingRate::MIN_CUSTOM, SamplingRate::MAX_CUSTOM)))
+        };
+
+        let log_interval_entry = parameters.take_required("log_interval")?;
+        let log_interval = log_interval_entry.parse::<usize>(PARAMETERS_SECTION, "expected a number of seconds")?;
+        if log_interval <= 2 {
+            return Err(log_interval_entry.invalid(PARAMETERS_SECTION, "must be greater than 2 seconds"));
+        }
+
+        let number_of_stations = parameters.take_required("number_of_stations")?.parse::<usize>(PARAMETERS_SECTION, "expected a number of stations")?;
+        let scaling_factor = parameters.take_required("scaling_factor")?.parse::<f64>(PARAMETERS_SECTION, "expected a decimal number")?;
+
+        let hourly_save = match parameters.take("hourly_save") {
+            Some(entry) => entry.parse_yes_no(PARAMETERS_SECTION)?,
+            None => false
+        };
+
+        let data_path = match parameters.take("data_path") {
+            Some(entry) => std::path::PathBuf::from(entry.value),
+            None => std::path::PathBuf::from(DEFAULT_DATA_PATH)
+        };
+
+        let log_format = match parameters.take("log_format") {
+            Some(entry) => match LogFormat::from_label(&entry.value) {
+                Some(log_format) => log_format,
+                None => return Err(entry.invalid(PARAMETERS_SECTION, "must be one of sid_format, sid_extended, supersid_format, supersid_extended, both or both_extended"))
+            },
+            None => LogFormat::SupersidExtended
+        };
+
+        let bema_wing = match parameters.take("bema_wing") {
+            Some(entry) => entry.parse::<usize>(PARAMETERS_SECTION, "expected a whole number")?,
+            None => DEFAULT_BEMA_WING
+        };
+
+        parameters.ensure_consumed()?;
+
+        let device_id = match capture.take("device") {
+            Some(entry) => entry.value,
+            None => DEFAULT_DEVICE.to_string()
+        };
+
+        let period_size = match capture.take("periodsize") {
+            Some(entry) => entry.parse::<usize>(CAPTURE_SECTION, "expected a number of frames")?,
+            None => DEFAULT_PERIOD_SIZE
+        };
+
+        let format = match capture.take("format") {
+            Some(entry) => match Format::from_label(&entry.value) {
+                Some(format) => format,
+                None => return Err(entry.invalid(CAPTURE_SECTION, "must be one of S16_LE, S24_3LE, S24_LE, S32_LE, FLOAT_LE or U16_LE"))
+            },
+            None => Format::B16
+        };
+
+        let channels = match capture.take("channels") {
+            Some(entry) => {
+                let channels = entry.parse::<usize>(CAPTURE_SECTION, "expected a number of channels")?;
+                if channels < 1 {
+                    return Err(entry.invalid(CAPTURE_SECTION, "at least one channel must be captured"));
+                }
+                channels
+            },
+            None => 1
+        };
+
+        capture.ensure_consumed()?;
+
+        let mut stations = Vec::<StationConfig>::with_capacity(number_of_stations);
+        let mut i: usize = 1;
+        while i <= number_of_stations {
+            let section_name = format!("{}{}", STATION_SECTION_PREFIX, i);
+            let mut section = match ini.take_section(&section_name) {
+                Some(section) => section,
+                None => return Err(ConfigError::MissingSection { section: section_name })
+            };
+
+            let callsign = section.take_required("call_sign")?.value;
+
+            let frequency_entry = section.take_required("frequency")?;
+            let frequency = frequency_entry.parse::<usize>(&section_name, "expected a frequency in Hz")?;
+            if frequency > sampling_rate.value() / 2 {
+                return Err(frequency_entry.invalid(&section_name, &format!("audio_sampling_rate = {} must be at least {}", sampling_rate.value(), frequency * 2)));
+            }
+
+            let color_entry = section.take_required("color")?;
+            let mut color_chars = color_entry.value.chars();
+            let color = match (color_chars.next(), color_chars.next()) {
+                (Some(color), None) => color,
+                _ => return Err(color_entry.invalid(&section_name, "expected a single character color code such as r, g, b, c, m, y or k"))
+            };
+
+            let mut station = StationConfig::new(&callsign, color, frequency);
+            if let Some(entry) = section.take("channel") {
+                station.channel = match StationChannel::from_label(&entry.value) {
+                    Some(channel) => channel,
+                    None => return Err(entry.invalid(&section_name, "expected a channel index, sum or max"))
+                };
+                if let StationChannel::Index(index) = station.channel {
+                    if index >= channels {
+                        return Err(entry.invalid(&section_name, &format!("must be lower than [{}] Channels = {}", CAPTURE_SECTION, channels)));
+                    }
+                }
+            }
+
+            section.ensure_consumed()?;
+            stations.push(station);
+            i += 1;
+        }
+
+        let automatic_upload = match ftp.take("automatic_upload") {
+            Some(entry) => entry.parse_yes_no(FTP_SECTION)?,
+            None => false
+        };
+
+        if automatic_upload && (log_format == LogFormat::SidFormat || log_format == LogFormat::SidExtended) {
+            return Err(ConfigError::InvalidValue {
+                section: PARAMETERS_SECTION.to_string(),
+                key: "log_format".to_string(),
+                value: log_format.label().to_string(),
+                reason: "must be one of supersid_format, supersid_extended, both or both_extended for automatic_upload = yes".to_string()
+            });
+        }
+
+        let ftp_config = FtpConfig {
+            automatic_upload,
+            ftp_server: ftp.take("ftp_server").map(|entry| entry.value).unwrap_or_default(),
+            ftp_directory: ftp.take("ftp_directory").map(|entry| entry.value).unwrap_or_default(),
+            local_tmp: std::path::PathBuf::from(ftp.take("local_tmp").map(|entry| entry.value).unwrap_or_else(|| DEFAULT_LOCAL_TMP.to_string())),
+            call_signs: match ftp.take("call_signs") {
+                Some(entry) => entry.value.split(',').map(|call_sign| call_sign.trim().to_string()).filter(|call_sign| !call_sign.is_empty()).collect(),
+                None => Vec::new()
+            }
+        };
+
+        ftp.ensure_consumed()?;
+
+        if let Some(section) = ini.sections.first() {
+            return Err(ConfigError::UnknownSection { line: section.line, section: section.name.clone() });
+        }
+
+        let config = Self {
+            monitor_id,
+            site,
+            sound_card: SoundCardConfig::new(&device_id, format, sampling_rate, period_size),
+            channels,
+            stations,
+            sample_integration_algorithm: SampleIntegrationAlgorithm::OneChannel,
+            log_interval,
+            log_type,
+            log_format,
+            scaling_factor,
+            hourly_save,
+            bema_wing,
+            data_path,
+            calibration: None,
+            ftp: ftp_config
+        };
+
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+
+struct LegacyEntry {
+    key: String,
+    value: String,
+    line: usize
+}
+
+impl LegacyEntry {
+    fn invalid(&self, section: &str, reason: &str) -> ConfigError {
+        ConfigError::InvalidValue {
+            section: section.to_string(),
+            key: self.key.clone(),
+            value: self.value.clone(),
+            reason: format!("{} (line {})", reason, self.line)
+        }
+    }
+
+    fn parse<V: std::str::FromStr>(&self, section: &str, reason: &str) -> Result<V, ConfigError> {
+        match self.value.parse::<V>() {
+            Ok(value) => Ok(value),
+            Err(_) => Err(self.invalid(section, reason))
+        }
+    }
+
+    fn parse_yes_no(&self, section: &str) -> Result<bool, ConfigError> {
+        match self.value.to_lowercase().as_str() {
+            "yes" => Ok(true),
+            "no" => Ok(false),
+            _ => Err(self.invalid(section, "must be either 'yes' or 'no'"))
+        }
+    }
+}
+
+
+struct LegacySection {
+    name: String,
+    line: usize,
+    entries: Vec<LegacyEntry>
+}
+
+impl LegacySection {
+    fn empty(name: &str) -> Self {
+        Self { name: name.to_string(), line: 0, entries: Vec::new() }
+    }
+
+    /// Removes the entry for `key`, so that whatever is left afterwards is unknown.
+    fn take(&mut self, key: &str) -> Option<LegacyEntry> {
+        match self.entries.iter().position(|entry| entry.key == key) {
+            Some(index) => Some(self.entries.remove(index)),
+            None => None
+        }
+    }
+
+    fn take_required(&mut self, key: &str) -> Result<LegacyEntry, ConfigError> {
+        match self.take(key) {
+            Some(entry) => Ok(entry),
+            None => Err(ConfigError::MissingKey { section: self.name.clone(), key: key.to_string() })
+        }
+    }
+
+    fn ensure_consumed(&self) -> Result<(), ConfigError> {
+        match self.entries.first() {
+            Some(entry) => Err(ConfigError::UnknownKey { line: entry.line, section: self.name.clone(), key: entry.key.clone() }),
+            None => Ok(())
+        }
+    }
+}
+
+
+struct LegacyIni {
+    sections: Vec<LegacySection>
+}
+
+impl LegacyIni {
+    /// Parses the INI dialect of Python's `configparser` with its default settings.
+    fn parse(contents: &str) -> Result<Self, ConfigError> {
+        let mut sections = Vec::<LegacySection>::new();
+
+        for (index, raw_line) in contents.lines().enumerate() {
+            let line_number = index + 1;
+            let line = raw_line.trim();
+
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if line.starts_with('[') {
+                if !line.ends_with(']') {
+                    return Err(ConfigError::Syntax { line: line_number, message: format!("malformed section header '{}'", line) });
+                }
+                let name = line[1..line.len() - 1].trim().to_string();
+                if sections.iter().any(|section| section.name == name) {
+                    return Err(ConfigError::Syntax { line: line_number, message: format!("section [{}] is defined twice", name) });
+                }
+                sections.push(LegacySection { name, line: line_number, entries: Vec::new() });
+                continue;
+            }
+
+            let section = match sections.last_mut() {
+                Some(section) => section,
+                None => return Err(ConfigError::Syntax { line: line_number, message: "key found before the first section header".to_string() })
+            };
+
+            // Indented lines continue the value of the previous key.
+            if raw_line.starts_with(char::is_whitespace) {
+                if let Some(entry) = section.entries.last_mut() {
+                    entry.value.push('\n');
+                    entry.value.push_str(line);
+                    continue;
+                }
+            }
+
+            let separator = match line.find(['=', ':']) {
+                Some(separator) => separator,
+                None => return Err(ConfigError::Syntax { line: line_number, message: format!("expected 'key = value' but found '{}'", line) })
+            };
+
+            let key = line[..separator].trim().to_lowercase();
+            let value = line[separator + 1..].trim().to_string();
+
+            if section.entries.iter().any(|entry| entry.key == key) {
+                return Err(ConfigError::Syntax { line: line_number, message: format!("'{}' is defined twice in [{}]", key, section.name) });
+            }
+            section.entries.push(LegacyEntry { key, value, line: line_number });
+        }
+
+        Ok(Self { sections })
+    }
+
+    fn take_section(&mut self, name: &str) -> Option<LegacySection> {
+        match self.sections.iter().position(|section| section.name == name) {
+            Some(index) => Some(self.sections.remove(index)),
+            None => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUNDLED: &str = include_str!("../../../Config/supersid.cfg");
+
+    /// Returns the bundled file, Windows line endings included, with `from` replaced by `to`.
+    fn bundled_with(from: &str, to: &str) -> String {
+        let contents = BUNDLED.replace("\r\n", "\n");
+        assert!(contents.contains(from), "'{}' is not in supersid.cfg", from);
+        contents.replacen(from, to, 1)
+    }
+
+    #[test]
+    fn loads_the_bundled_file() {
+        let config = SuperSidConfig::from_legacy_file("Config/supersid.cfg").unwrap();
+
+        assert_eq!(config.monitor_id, "SAMPLE1");
+        assert_eq!(config.site.site_name, "EXAMPLE");
+        assert_eq!(config.site.site_contact_email, "you@domain.tld");
+        assert_eq!((config.site.site_latitude, config.site.site_longitude), (51.478, -0.001));
+        assert_eq!(config.sound_card.device_id, "plughw:CARD=Generic,DEV=0");
+        assert!(matches!(config.sound_card.format, Format::B16));
+        assert_eq!(config.sound_card.sampling_rate.value(), 48000);
+        assert_eq!((config.sound_card.period_size, config.channels), (1024, 1));
+        assert_eq!((config.log_interval, config.log_type, config.log_format), (5, LogType::Filtered, LogFormat::SupersidExtended));
+        assert!(config.hourly_save);
+        assert_eq!(config.data_path, std::path::Path::new("Config").join("../Data"));
+        assert!(!config.ftp.automatic_upload);
+        assert_eq!(config.ftp.call_signs, vec!["NWC".to_string()]);
+    }
+
+    #[test]
+    fn reads_the_stations() {
+        let config = SuperSidConfig::from_legacy_str(BUNDLED).unwrap();
+        let stations: Vec<(&str, char, usize, StationChannel)> = config.stations.iter().map(|station| (station.callsign.as_str(), station.color, station.frequency, station.channel)).collect();
+        assert_eq!(stations, vec![("NWC", 'y', 19800, StationChannel::Index(0)), ("VTX3", 'k', 18200, StationChannel::Index(0))]);
+
+        let stereo = bundled_with("Channels = 1", "Channels = 2").replacen("channel = 0", "channel = 1", 1).replacen("channel = 0", "channel = max", 1);
+        let config = SuperSidConfig::from_legacy_str(&stereo).unwrap();
+        assert_eq!(config.stations[0].channel, StationChannel::Index(1));
+        assert_eq!(config.stations[1].channel, StationChannel::Combined(crate::supersid::config::ChannelCombination::Max));
+
+        // The channel is optional and defaults to the first one.
+        let config = SuperSidConfig::from_legacy_str(&bundled_with("channel = 0\n", "")).unwrap();
+        assert_eq!(config.stations[0].channel, StationChannel::Index(0));
+    }
+
+    #[test]
+    fn rejects_channels_that_are_not_captured() {
+        match SuperSidConfig::from_legacy_str(&bundled_with("channel = 0", "channel = 1")) {
+            Err(ConfigError::InvalidValue { section, key, .. }) => assert_eq!((section.as_str(), key.as_str()), ("STATION_1", "channel")),
+            other => panic!("expected an invalid channel, got {:?}", other)
+        }
+        match SuperSidConfig::from_legacy_str(&bundled_with("channel = 0", "channel = left")) {
+            Err(ConfigError::InvalidValue { key, .. }) => assert_eq!(key, "channel"),
+            other => panic!("expected an invalid channel, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn rejects_missing_stations() {
+        match SuperSidConfig::from_legacy_str(&bundled_with("number_of_stations = 2", "number_of_stations = 3")) {
+            Err(ConfigError::MissingSection { section }) => assert_eq!(section, "STATION_3"),
+            other => panic!("expected a missing section, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn rejects_a_missing_required_key() {
+        match SuperSidConfig::from_legacy_str(&bundled_with("monitor_id = SAMPLE1\n", "")) {
+            Err(ConfigError::MissingKey { section, key }) => assert_eq!((section.as_str(), key.as_str()), ("PARAMETERS", "monitor_id")),
+            other => panic!("expected a missing key, got {:?}", other)
+        }
+        match SuperSidConfig::from_legacy_str(&bundled_with("call_sign = VTX3\n", "")) {
+            Err(ConfigError::MissingKey { section, key }) => assert_eq!((section.as_str(), key.as_str()), ("STATION_2", "call_sign")),
+            other => panic!("expected a missing key, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn rejects_an_unknown_key() {
+        match SuperSidConfig::from_legacy_str(&bundled_with("viewer = text\n", "viewer = text\nvolume = 11\n")) {
+            Err(ConfigError::UnknownKey { line, section, key }) => assert_eq!((line, section.as_str(), key.as_str()), (8, "PARAMETERS", "volume")),
+            other => panic!("expected an unknown key, got {:?}", other)
+        }
+        match SuperSidConfig::from_legacy_str(&format!("{}\n[Plot]\nwidth = 3\n", BUNDLED)) {
+            Err(ConfigError::UnknownSection { section, .. }) => assert_eq!(section, "Plot"),
+            other => panic!("expected an unknown section, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn rejects_bad_numbers() {
+        match SuperSidConfig::from_legacy_str(&bundled_with("audio_sampling_rate = 48000", "audio_sampling_rate = 48 kHz")) {
+            Err(ConfigError::InvalidValue { key, value, .. }) => assert_eq!((key.as_str(), value.as_str()), ("audio_sampling_rate", "48 kHz")),
+            other => panic!("expected an invalid value, got {:?}", other)
+        }
+        match SuperSidConfig::from_legacy_str(&bundled_with("frequency = 19800", "frequency = 19.8")) {
+            Err(ConfigError::InvalidValue { section, key, .. }) => assert_eq!((section.as_str(), key.as_str()), ("STATION_1", "frequency")),
+            other => panic!("expected an invalid value, got {:?}", other)
+        }
+        match SuperSidConfig::from_legacy_str(&bundled_with("frequency = 19800", "frequency = 25000")) {
+            Err(ConfigError::InvalidValue { key, reason, .. }) => assert!(key == "frequency" && reason.contains("must be at least 50000"), "{}", reason),
+            other => panic!("expected a frequency above Nyquist, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn parses_the_configparser_dialect() {
+        let contents = "[PARAMETERS]\n; comment\nsite_name: EXAMPLE\nCONTACT = you@domain.tld\n  second line\n";
+        let mut ini = LegacyIni::parse(contents).unwrap();
+        let mut parameters = ini.take_section(PARAMETERS_SECTION).unwrap();
+        assert_eq!(parameters.take("site_name").unwrap().value, "EXAMPLE");
+        assert_eq!(parameters.take("contact").unwrap().value, "you@domain.tld\nsecond line");
+
+        match LegacyIni::parse("[PARAMETERS]\nsite_name = A\nsite_name = B\n") {
+            Err(ConfigError::Syntax { line, .. }) => assert_eq!(line, 3),
+            other => panic!("expected a syntax error, got {:?}", other.map(|_| ()))
+        }
+    }
+}
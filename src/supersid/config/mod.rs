@@ -1,7 +1,89 @@
-#[derive(Debug, ::serde::Serialize, ::serde::Deserialize)]
+pub mod legacy;
+pub mod native;
+pub mod transmitters;
+pub mod wizard;
+
+#[derive(Debug, Clone, Copy, PartialEq, ::serde::Serialize, ::serde::Deserialize)]
 pub enum SampleIntegrationAlgorithm {
-    OneChannel, // Ignores second channel produces a 1 second integration every second.
-    TwoChannel, // Alternates channels to produce a 2 second integration every second.
+    OneChannel, // Integrates each channel over 1 second, every second.
+    TwoChannel, // One antenna on both inputs, alternates channels to produce a 2 second integration every second.
+}
+
+/// Layout of the data files written by the logger (`log_format` in supersid.cfg).
+#[derive(Debug, Clone, Copy, PartialEq, ::serde::Serialize, ::serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    SidFormat,        // One file per station, timestamp with log_interval increment.
+    SidExtended,      // One file per station, extended timestamp.
+    SupersidFormat,   // All stations in one file, no timestamp.
+    SupersidExtended, // All stations in one file, extended timestamp.
+    Both,             // SidFormat and SupersidFormat.
+    BothExtended,     // SidExtended and SupersidExtended.
+}
+
+impl LogFormat {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::SidFormat => "sid_format",
+            Self::SidExtended => "sid_extended",
+            Self::SupersidFormat => "supersid_format",
+            Self::SupersidExtended => "supersid_extended",
+            Self::Both => "both",
+            Self::BothExtended => "both_extended"
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label.to_lowercase().as_str() {
+            "sid_format" => Some(Self::SidFormat),
+            "sid_extended" => Some(Self::SidExtended),
+            "supersid_format" => Some(Self::SupersidFormat),
+            "supersid_extended" => Some(Self::SupersidExtended),
+            "both" => Some(Self::Both),
+            "both_extended" => Some(Self::BothExtended),
+            _ => None
+        }
+    }
+
+    /// Whether one SID file is written per station.
+    pub fn writes_sid(&self) -> bool {
+        !matches!(self, Self::SupersidFormat | Self::SupersidExtended)
+    }
+
+    /// Whether one SuperSID file is written for all stations.
+    pub fn writes_supersid(&self) -> bool {
+        !matches!(self, Self::SidFormat | Self::SidExtended)
+    }
+
+    /// Whether the timestamps have microseconds (and SuperSID files have timestamps at all).
+    pub fn is_extended(&self) -> bool {
+        matches!(self, Self::SidExtended | Self::SupersidExtended | Self::BothExtended)
+    }
+}
+
+/// Whether the logged data is smoothed before being written (`log_type` in supersid.cfg).
+#[derive(Debug, Clone, Copy, PartialEq, ::serde::Serialize, ::serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogType {
+    Filtered,
+    Raw,
+}
+
+impl LogType {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Filtered => "filtered",
+            Self::Raw => "raw"
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label.to_lowercase().as_str() {
+            "filtered" => Some(Self::Filtered),
+            "raw" => Some(Self::Raw),
+            _ => None
+        }
+    }
 }
 
 #[derive(Debug, ::serde::Serialize, ::serde::Deserialize)]
@@ -9,17 +91,124 @@ pub struct SuperSidConfig {
     pub monitor_id: String,
     pub site: SuperSidSite,
     pub sound_card: crate::sound_card::config::SoundCardConfig,
+    pub channels: usize,
     pub stations: Vec<StationConfig>,
-    pub sample_integration_algorith: SampleIntegrationAlgorithm,
-    
-
-
+    #[serde(alias = "sample_integration_algorith")]
+    pub sample_integration_algorithm: SampleIntegrationAlgorithm,
+    pub log_interval: usize,
+    pub log_type: LogType,
+    pub log_format: LogFormat,
+    pub scaling_factor: f64,
+    pub hourly_save: bool,
+    pub bema_wing: usize,
+    pub data_path: std::path::PathBuf,
+    /// Correction curve written by `supersid calibrate`, applied to the station readings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub calibration: Option<std::path::PathBuf>,
+    #[serde(default)]
+    pub ftp: FtpConfig,
 }
 
 impl SuperSidConfig {
-    // read config
-    // prompt for new config
-    // save config
+    /// Checks the values that serde cannot check on its own.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let nyquist = self.sound_card.sampling_rate.value() / 2;
+
+        if self.monitor_id.trim().is_empty() {
+            return Err(ConfigError::Validation { field: "monitor_id".to_string(), reason: "must not be empty".to_string() });
+        }
+
+        if !(-90f64..=90f64).contains(&self.site.site_latitude) {
+            return Err(ConfigError::Validation { field: "site.site_latitude".to_string(), reason: format!("{} is not within -90 and 90 degrees", self.site.site_latitude) });
+        }
+
+        if !(-180f64..=180f64).contains(&self.site.site_longitude) {
+            return Err(ConfigError::Validation { field: "site.site_longitude".to_string(), reason: format!("{} is not within -180 and 180 degrees", self.site.site_longitude) });
+        }
+
+        if !self.sound_card.sampling_rate.is_valid() {
+            return Err(ConfigError::Validation {
+                field: "sound_card.sampling_rate".to_string(),
+                reason: format!("{} Hz is not within {} and {} Hz", self.sound_card.sampling_rate.value(), crate::sound_card::config::SamplingRate::MIN_CUSTOM, crate::sound_card::config::SamplingRate::MAX_CUSTOM)
+            });
+        }
+
+        if self.channels < 1 {
+            return Err(ConfigError::Validation { field: "channels".to_string(), reason: "at least one channel must be captured".to_string() });
+        }
+
+        if self.sample_integration_algorithm == SampleIntegrationAlgorithm::TwoChannel && self.channels != 2 {
+            return Err(ConfigError::Validation { field: "channels".to_string(), reason: "TwoChannel alternates between exactly 2 channels".to_string() });
+        }
+
+        // Frames read past one capture are dropped, a longer period would lose data every reading.
+        let capture_frames = self.sound_card.sampling_rate.value() * super::SuperSid::<i16, f64>::CAPTURE_MILLISECONDS / 1000;
+        if self.sound_card.period_size < 1 || self.sound_card.period_size > capture_frames {
+            return Err(ConfigError::Validation {
+                field: "sound_card.period_size".to_string(),
+                reason: format!("{} frames do not fit the {} frames of one capture at {} Hz", self.sound_card.period_size, capture_frames, self.sound_card.sampling_rate.value())
+            });
+        }
+
+        if self.sound_card.buffer_periods < 2 {
+            return Err(ConfigError::Validation { field: "sound_card.buffer_periods".to_string(), reason: "must be at least 2 periods".to_string() });
+        }
+
+        if self.log_interval <= 2 {
+            return Err(ConfigError::Validation { field: "log_interval".to_string(), reason: "must be greater than 2 seconds".to_string() });
+        }
+
+        if self.stations.is_empty() {
+            return Err(ConfigError::Validation { field: "stations".to_string(), reason: "at least one station must be monitored".to_string() });
+        }
+
+        for (i, station) in self.stations.iter().enumerate() {
+            if station.callsign.trim().is_empty() {
+                return Err(ConfigError::Validation { field: format!("stations[{}].callsign", i), reason: "must not be empty".to_string() });
+            }
+            if !StationConfig::COLORS.contains(&station.color) {
+                return Err(ConfigError::Validation { field: format!("stations[{}].color", i), reason: format!("'{}' is not one of {:?}", station.color, StationConfig::COLORS) });
+            }
+            if station.frequency > nyquist {
+                return Err(ConfigError::Validation {
+                    field: format!("stations[{}].frequency", i),
+                    reason: format!("{} Hz is above the Nyquist frequency of {} Hz for a {} Hz sampling rate", station.frequency, nyquist, self.sound_card.sampling_rate.value())
+                });
+            }
+            if let StationChannel::Index(index) = station.channel {
+                if index >= self.channels {
+                    return Err(ConfigError::Validation { field: format!("stations[{}].channel", i), reason: format!("must be lower than the {} captured channel(s)", self.channels) });
+                }
+            }
+            if self.sample_integration_algorithm == SampleIntegrationAlgorithm::TwoChannel && station.channel != StationChannel::Index(0) {
+                return Err(ConfigError::Validation { field: format!("stations[{}].channel", i), reason: "must be 0, TwoChannel reads one antenna wired to both inputs".to_string() });
+            }
+        }
+
+        if self.ftp.automatic_upload && (self.log_format == LogFormat::SidFormat || self.log_format == LogFormat::SidExtended) {
+            return Err(ConfigError::Validation { field: "log_format".to_string(), reason: "automatic upload needs one of the supersid or both formats".to_string() });
+        }
+
+        Ok(())
+    }
+
+    /// Resolves relative `data_path`, `calibration` and `local_tmp` values against the directory
+    /// of `config_path`.
+    fn resolve_paths(&mut self, config_path: &std::path::Path) {
+        if let Some(config_dir) = config_path.parent() {
+            if self.data_path.is_relative() && !self.data_path.as_os_str().is_empty() {
+                self.data_path = config_dir.join(&self.data_path);
+            }
+            if let Some(calibration) = &self.calibration {
+                if calibration.is_relative() {
+                    self.calibration = Some(config_dir.join(calibration));
+                }
+            }
+            if self.ftp.local_tmp.is_relative() && !self.ftp.local_tmp.as_os_str().is_empty() {
+                self.ftp.local_tmp = config_dir.join(&self.ftp.local_tmp);
+            }
+        }
+    }
 }
 
 
@@ -29,7 +218,8 @@ pub struct SuperSidSite {
     pub site_contact_email: String,
     pub site_latitude: f64,
     pub site_longitude: f64,
-
+    pub utc_offset: String,
+    pub time_zone: String,
 }
 
 
@@ -38,7 +228,72 @@ impl SuperSidSite {
         Self { site_name: site_name,
             site_contact_email: site_contact_email,
             site_latitude: site_latitude,
-            site_longitude: site_longitude
+            site_longitude: site_longitude,
+            utc_offset: "+00:00".to_string(),
+            time_zone: "UTC".to_string()
+        }
+    }
+}
+
+/// Upload settings for the Stanford SuperSID archive ([FTP] section of supersid.cfg).
+#[derive(Debug, Default, ::serde::Serialize, ::serde::Deserialize)]
+pub struct FtpConfig {
+    pub automatic_upload: bool,
+    pub ftp_server: String,
+    pub ftp_directory: String,
+    pub local_tmp: std::path::PathBuf,
+    pub call_signs: Vec<String>,
+}
+
+/// Channels a station is read from (`channel` of a `[STATION_n]` in supersid.cfg).
+#[derive(Debug, Clone, Copy, PartialEq, ::serde::Serialize, ::serde::Deserialize)]
+#[serde(untagged)]
+pub enum StationChannel {
+    Index(usize),                  // One captured channel, 0 being the left one.
+    Combined(ChannelCombination),  // All captured channels combined.
+}
+
+impl Default for StationChannel {
+    fn default() -> Self { Self::Index(0) }
+}
+
+impl StationChannel {
+    /// Value as written in configuration files: the channel index, `sum` or `max`.
+    pub fn label(&self) -> String {
+        match self {
+            Self::Index(index) => index.to_string(),
+            Self::Combined(combination) => combination.label().to_string()
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label.parse::<usize>() {
+            Ok(index) => Some(Self::Index(index)),
+            Err(_) => ChannelCombination::from_label(label).map(Self::Combined)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ::serde::Serialize, ::serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelCombination {
+    Sum, // Sum of the spectral densities, the power received by both antennas.
+    Max, // Highest spectral density, the antenna best aligned with the transmitter.
+}
+
+impl ChannelCombination {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Sum => "sum",
+            Self::Max => "max"
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label {
+            "sum" => Some(Self::Sum),
+            "max" => Some(Self::Max),
+            _ => None
         }
     }
 }
@@ -47,21 +302,158 @@ impl SuperSidSite {
 pub struct StationConfig{
     pub callsign: String,
     pub color: char,
-    pub frequency: usize
+    pub frequency: usize,
+    #[serde(default)]
+    pub channel: StationChannel
 }
 
 impl StationConfig{
+    /// Single letter colors understood by the plotting scripts.
+    pub const COLORS: [char; 8] = ['b', 'g', 'r', 'c', 'm', 'y', 'k', 'w'];
+
+    /// Creates a station read from the first channel.
     pub fn new(callsign: &str, color: char, frequency: usize) -> Self {
         Self {
             callsign: callsign.to_string(),
             color: color,
-            frequency: frequency
+            frequency: frequency,
+            channel: StationChannel::default()
         }
     }
-    
+
+    /// Returns the reading of the station from the spectral densities of the captured channels,
+    /// or `None` when its channel was not captured or its frequency is out of the spectrum.
+    pub fn read<T: crate::spectral_density::Measurement>(&self, spectra: &[crate::spectral_density::SpectralDensity<T>]) -> Option<crate::spectral_density::SpectralDensitySample<T, T>> {
+        let sample = |spectrum: &crate::spectral_density::SpectralDensity<T>| spectrum.data.iter().nth(self.get_bin(spectrum.freq_step)).copied();
+        match self.channel {
+            StationChannel::Index(index) => spectra.get(index).and_then(sample),
+            StationChannel::Combined(combination) => {
+                let mut combined: Option<crate::spectral_density::SpectralDensitySample<T, T>> = None;
+                for spectrum in spectra.iter() {
+                    let current = sample(spectrum)?;
+                    combined = match (combined, combination) {
+                        (None, _) => Some(current),
+                        (Some(total), ChannelCombination::Sum) => Some(crate::spectral_density::SpectralDensitySample::new(total.frequency(), total.spectral_density() + current.spectral_density())),
+                        (Some(best), ChannelCombination::Max) => Some(if current.spectral_density() > best.spectral_density() { current } else { best })
+                    };
+                }
+                combined
+            }
+        }
+    }
+
     #[allow(non_snake_case)]
     pub fn get_bin<T: crate::spectral_density::Measurement>(&self, freq_per_step: T) -> usize {
         let freq_t = T::from(self.frequency).unwrap();
         (freq_t / freq_per_step).to_usize().unwrap() + if freq_t % freq_per_step > freq_per_step / T::from(2).unwrap() { 1 } else { 0 }
     }
-}
\ No newline at end of file
+}
+
+/// Errors raised while loading or validating a configuration file.
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Syntax { line: usize, message: String },
+    UnknownSection { line: usize, section: String },
+    UnknownKey { line: usize, section: String, key: String },
+    MissingSection { section: String },
+    MissingKey { section: String, key: String },
+    InvalidValue { section: String, key: String, value: String, reason: String },
+    Parse(String),
+    Serialize(String),
+    Validation { field: String, reason: String },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "Could not read configuration: {}", error),
+            Self::Syntax { line, message } => write!(f, "Line {}: {}", line, message),
+            Self::UnknownSection { line, section } => write!(f, "Line {}: unknown section [{}]", line, section),
+            Self::UnknownKey { line, section, key } => write!(f, "Line {}: unknown key '{}' in [{}]", line, key, section),
+            Self::MissingSection { section } => write!(f, "Section [{}] is mandatory but missing", section),
+            Self::MissingKey { section, key } => write!(f, "'{}' is mandatory but missing from [{}]", key, section),
+            Self::InvalidValue { section, key, value, reason } => write!(f, "[{}] {} = '{}': {}", section, key, value, reason),
+            Self::Parse(message) => write!(f, "Could not parse configuration: {}", message),
+            Self::Serialize(message) => write!(f, "Could not write configuration: {}", message),
+            Self::Validation { field, reason } => write!(f, "'{}' {}", field, reason)
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+#[cfg(test)]
+impl SuperSidConfig {
+    /// Configuration of the tests: `stations` read at 48 kHz from `device_id`, raw files written
+    /// to `data_path`.
+    pub fn for_tests(device_id: &str, channels: usize, stations: Vec<StationConfig>, data_path: &std::path::Path) -> Self {
+        use crate::sound_card::config::{Format, SamplingRate, SoundCardConfig};
+
+        Self {
+            monitor_id: "TEST".to_string(),
+            site: SuperSidSite::new("TESTSITE".to_string(), "test@example.com".to_string(), 37.43, -122.17),
+            sound_card: SoundCardConfig::new(device_id, Format::B16, SamplingRate::Hz48000, legacy::DEFAULT_PERIOD_SIZE),
+            channels,
+            stations,
+            sample_integration_algorithm: SampleIntegrationAlgorithm::OneChannel,
+            log_interval: 5,
+            log_type: LogType::Raw,
+            log_format: LogFormat::SupersidExtended,
+            scaling_factor: 1.0,
+            hourly_save: false,
+            bema_wing: legacy::DEFAULT_BEMA_WING,
+            data_path: data_path.to_path_buf(),
+            calibration: None,
+            ftp: FtpConfig::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn two_stations(channels: usize) -> SuperSidConfig {
+        let mut stations = vec![StationConfig::new("NAA", 'r', 24000), StationConfig::new("NWC", 'b', 19800)];
+        stations[1].channel = StationChannel::Index(channels - 1);
+        SuperSidConfig::for_tests("hw:0,0", channels, stations, std::path::Path::new("."))
+    }
+
+    fn invalid_field(config: &SuperSidConfig) -> String {
+        match config.validate() {
+            Err(ConfigError::Validation { field, .. }) => field,
+            other => panic!("expected a validation error, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn one_channel_reads_every_assigned_channel() {
+        let mut config = two_stations(2);
+        assert_eq!(config.sample_integration_algorithm, SampleIntegrationAlgorithm::OneChannel);
+        assert!(config.validate().is_ok());
+
+        config.stations[1].channel = StationChannel::Combined(ChannelCombination::Max);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn two_channel_reads_one_antenna_on_two_inputs() {
+        let mut config = two_stations(2);
+        config.sample_integration_algorithm = SampleIntegrationAlgorithm::TwoChannel;
+        assert_eq!(invalid_field(&config), "stations[1].channel");
+
+        config.stations[1].channel = StationChannel::Index(0);
+        assert!(config.validate().is_ok());
+
+        let mut config = two_stations(1);
+        config.sample_integration_algorithm = SampleIntegrationAlgorithm::TwoChannel;
+        assert_eq!(invalid_field(&config), "channels");
+    }
+}
@@ -0,0 +1,258 @@
+//! Native TOML and JSON configuration files, written straight from the serde derives.
+
+use super::{ConfigError, SuperSidConfig};
+
+/// File format of a configuration file, derived from its extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFileFormat {
+    Legacy, // supersid.cfg INI file of the Python monitor.
+    Toml,
+    Json,
+}
+
+impl ConfigFileFormat {
+    pub fn from_path(path: &std::path::Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()).map(|extension| extension.to_lowercase()) {
+            Some(extension) if extension == "toml" => Self::Toml,
+            Some(extension) if extension == "json" => Self::Json,
+            _ => Self::Legacy
+        }
+    }
+}
+
+// Comments written above each key (or table header) of a saved TOML file, keyed by table and key.
+const TOML_COMMENTS: [(&str, &str, &str); 32] = [
+    ("", "monitor_id", "Unique id to distinguish the monitors running on one site."),
+    ("", "channels", "Number of channels captured from the sound card."),
+    ("", "sample_integration_algorithm", "OneChannel (1 s of each channel) or TwoChannel (one antenna on 2 channels taking turns, 2 s, lower noise)."),
+    ("", "log_interval", "Seconds between two readings, must be greater than 2."),
+    ("", "log_type", "filtered (smoothed before writing) or raw."),
+    ("", "log_format", "sid_format, sid_extended, supersid_format, supersid_extended, both or both_extended."),
+    ("", "scaling_factor", "Captured values are multiplied by this factor."),
+    ("", "hourly_save", "Write a raw checkpoint file every hour."),
+    ("", "bema_wing", "Wing size of the smoothing filter used for filtered files."),
+    ("", "data_path", "Directory the data files are written to."),
+    ("", "calibration", "Correction curve of the sound card written by supersid calibrate, optional."),
+    ("", "site", "Identification of the monitor."),
+    ("site", "site_name", "Unique identification of the site."),
+    ("site", "site_contact_email", "Email or phone number of the owner."),
+    ("site", "site_latitude", "Decimal degrees, -90 to 90."),
+    ("site", "site_longitude", "Decimal degrees, -180 to 180."),
+    ("site", "utc_offset", "Offset of the local time, for instance +01:00."),
+    ("site", "time_zone", "Name of the local time zone."),
+    ("", "sound_card", "Capture device."),
+    ("sound_card", "device_id", "ALSA PCM name, for instance plughw:CARD=Generic,DEV=0, file:<path> of a WAV or raw file, or synthetic:<Hz>[@<amplitude>],... to generate tones."),
+    ("sound_card", "format", "B16 (S16_LE), B24 (S24_3LE), B24In32 (S24_LE), B32 (S32_LE), F32 (FLOAT_LE) or U16 (U16_LE)."),
+    ("sound_card", "sampling_rate", "Hz44100, Hz48000, Hz96000, Hz192000 or { Custom = <Hz> } for another rate."),
+    ("sound_card", "period_size", "Frames per ALSA period, the device may grant a nearby size."),
+    ("sound_card", "buffer_periods", "Periods held by the ALSA ring buffer, at least 2."),
+    ("", "ftp", "Upload to the Stanford SuperSID archive."),
+    ("ftp", "automatic_upload", "Upload the previous day's file after midnight UTC."),
+    ("ftp", "local_tmp", "Directory the upload files are prepared in."),
+    ("ftp", "call_signs", "Stations to upload."),
+    ("", "stations", "One table per monitored station."),
+    ("stations", "color", "Plot color: b, g, r, c, m, y, k or w."),
+    ("stations", "frequency", "Transmitter frequency in Hz, below half the sampling rate."),
+    ("stations", "channel", "Captured channel read (0 = left, 1 = right), or \"sum\" or \"max\" of all channels."),
+];
+
+impl SuperSidConfig {
+    /// Loads and validates a configuration file, picking the parser from the file extension.
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let format = ConfigFileFormat::from_path(path);
+
+        if format == ConfigFileFormat::Legacy {
+            return Self::from_legacy_file(path);
+        }
+
+        let contents = std::fs::read_to_string(path)?;
+        let mut config = match format {
+            ConfigFileFormat::Json => Self::from_json_str(&contents)?,
+            _ => Self::from_toml_str(&contents)?
+        };
+        config.resolve_paths(path);
+        Ok(config)
+    }
+
+    /// Validates and writes the configuration as TOML, or as JSON for a `.json` path.
+    pub fn save<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), ConfigError> {
+        let path = path.as_ref();
+        let contents = match ConfigFileFormat::from_path(path) {
+            ConfigFileFormat::Json => self.to_json_string()?,
+            ConfigFileFormat::Toml => self.to_toml_string()?,
+            ConfigFileFormat::Legacy => return Err(ConfigError::Serialize(format!("'{}' must have a .toml or .json extension", path.display())))
+        };
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+
+    pub fn from_toml_str(contents: &str) -> Result<Self, ConfigError> {
+        let config: Self = match toml::from_str(contents) {
+            Ok(config) => config,
+            Err(error) => return Err(ConfigError::Parse(error.to_string()))
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_json_str(contents: &str) -> Result<Self, ConfigError> {
+        let config: Self = match serde_json::from_str(contents) {
+            Ok(config) => config,
+            Err(error) => return Err(ConfigError::Parse(error.to_string()))
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Returns the canonical TOML form of the configuration, with a comment above each known key.
+    pub fn to_toml_string(&self) -> Result<String, ConfigError> {
+        self.validate()?;
+
+        let plain = match toml::to_string_pretty(self) {
+            Ok(plain) => plain,
+            Err(error) => return Err(ConfigError::Serialize(error.to_string()))
+        };
+
+        let mut commented = String::with_capacity(plain.len() * 2);
+        commented.push_str("# SuperSID configuration\n\n");
+
+        let mut table = "";
+        let mut repeated_table = false;
+        let mut seen_tables = Vec::<&str>::new();
+        for line in plain.lines() {
+            let trimmed = line.trim();
+            let comment = if trimmed.starts_with('[') {
+                table = trimmed.trim_matches(|c| c == '[' || c == ']');
+                // Arrays of tables repeat their header, only the first one is commented.
+                repeated_table = seen_tables.contains(&table);
+                seen_tables.push(table);
+                if repeated_table { None } else { Self::toml_comment("", table) }
+            } else {
+                match trimmed.split_once(" = ") {
+                    Some((key, _)) if !repeated_table && !line.starts_with(char::is_whitespace) => Self::toml_comment(table, key),
+                    _ => None
+                }
+            };
+
+            if let Some(comment) = comment {
+                commented.push_str("# ");
+                commented.push_str(comment);
+                commented.push('\n');
+            }
+            commented.push_str(line);
+            commented.push('\n');
+        }
+
+        Ok(commented)
+    }
+
+    pub fn to_json_string(&self) -> Result<String, ConfigError> {
+        self.validate()?;
+
+        match serde_json::to_string_pretty(self) {
+            Ok(json) => Ok(json + "\n"),
+            Err(error) => Err(ConfigError::Serialize(error.to_string()))
+        }
+    }
+
+    fn toml_comment(table: &str, key: &str) -> Option<&'static str> {
+        TOML_COMMENTS.iter()
+            .find(|(comment_table, comment_key, _)| *comment_table == table && *comment_key == key)
+            .map(|(_, _, comment)| *comment)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the bundled supersid.cfg with absolute paths, which load back unchanged.
+    fn bundled_config() -> SuperSidConfig {
+        let mut config = SuperSidConfig::from_legacy_file("Config/supersid.cfg").unwrap();
+        config.data_path = std::env::temp_dir();
+        config.ftp.local_tmp = std::env::temp_dir();
+        config
+    }
+
+    fn round_trip(extension: &str) -> String {
+        let config = bundled_config();
+        let path = std::env::temp_dir().join(format!("supersid_native_{}.{}", std::process::id(), extension));
+        config.save(&path).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let loaded = SuperSidConfig::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(serde_json::to_value(&loaded).unwrap(), serde_json::to_value(&config).unwrap());
+        contents
+    }
+
+    fn rejected_field(config: &SuperSidConfig) -> String {
+        match config.validate() {
+            Err(ConfigError::Validation { field, .. }) => field,
+            other => panic!("expected a validation error, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        let contents = round_trip("toml");
+        assert!(contents.starts_with("# SuperSID configuration\n"));
+        assert!(contents.contains("# Unique id to distinguish the monitors running on one site.\nmonitor_id = \"SAMPLE1\"\n"));
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let contents = round_trip("json");
+        assert!(contents.contains("\"monitor_id\": \"SAMPLE1\""));
+    }
+
+    #[test]
+    fn stations_above_nyquist_are_rejected() {
+        let mut config = bundled_config();
+        config.stations[1].frequency = 24001;
+        assert_eq!(rejected_field(&config), "stations[1].frequency");
+
+        config.stations[1].frequency = 24000;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn unknown_colors_are_rejected() {
+        let mut config = bundled_config();
+        config.stations[0].color = 'x';
+        assert_eq!(rejected_field(&config), "stations[0].color");
+    }
+
+    #[test]
+    fn coordinates_out_of_range_are_rejected() {
+        let mut config = bundled_config();
+        config.site.site_latitude = 90.5;
+        assert_eq!(rejected_field(&config), "site.site_latitude");
+
+        let mut config = bundled_config();
+        config.site.site_longitude = -180.5;
+        assert_eq!(rejected_field(&config), "site.site_longitude");
+    }
+
+    #[test]
+    fn periods_longer_than_a_capture_are_rejected() {
+        let mut config = bundled_config();
+        config.sound_card.period_size = 48001;
+        assert_eq!(rejected_field(&config), "sound_card.period_size");
+
+        config.sound_card.period_size = 0;
+        assert_eq!(rejected_field(&config), "sound_card.period_size");
+    }
+
+    #[test]
+    fn loading_validates_the_file() {
+        let mut config = bundled_config();
+        config.stations[0].color = 'x';
+        let contents = toml::to_string(&config).unwrap();
+        match SuperSidConfig::from_toml_str(&contents) {
+            Err(ConfigError::Validation { field, .. }) => assert_eq!(field, "stations[0].color"),
+            other => panic!("expected a validation error, got {:?}", other)
+        }
+    }
+}
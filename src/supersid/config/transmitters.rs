@@ -0,0 +1,59 @@
+//! Well known VLF transmitters, used to suggest stations for a new site.
+//!
+//! Sources: https://www.mwlist.org/vlf.php and https://sidstation.loudet.org/stations-list-en.xhtml
+//! (the latter wins in case of contradictions, as in the sample supersid.cfg).
+
+#[derive(Debug, Clone, Copy)]
+pub struct VlfTransmitter {
+    pub callsign: &'static str,
+    pub frequency: usize,
+    pub location: &'static str,
+    pub latitude: f64,
+    pub longitude: f64
+}
+
+impl VlfTransmitter {
+    const EARTH_RADIUS_KM: f64 = 6371.0;
+
+    /// Great circle distance in km from the given coordinates in decimal degrees.
+    pub fn distance_km(&self, latitude: f64, longitude: f64) -> f64 {
+        let (lat_1, lat_2) = (latitude.to_radians(), self.latitude.to_radians());
+        let delta_lat = lat_2 - lat_1;
+        let delta_lon = (self.longitude - longitude).to_radians();
+        let a = (delta_lat / 2.).sin().powi(2) + lat_1.cos() * lat_2.cos() * (delta_lon / 2.).sin().powi(2);
+        2. * Self::EARTH_RADIUS_KM * a.sqrt().asin()
+    }
+}
+
+pub const VLF_TRANSMITTERS: [VlfTransmitter; 20] = [
+    VlfTransmitter { callsign: "VTX2", frequency: 17000, location: "South Vijayanarayanam, India", latitude: 8.387015, longitude: 77.752762 },
+    VlfTransmitter { callsign: "SAQ", frequency: 17200, location: "Grimeton, Sweden", latitude: 57.1056, longitude: 12.3969 },
+    VlfTransmitter { callsign: "VTX3", frequency: 18200, location: "South Vijayanarayanam, India", latitude: 8.387015, longitude: 77.752762 },
+    VlfTransmitter { callsign: "HWU", frequency: 18300, location: "Rosnay, France", latitude: 46.7130, longitude: 1.2447 },
+    VlfTransmitter { callsign: "GBZ", frequency: 19580, location: "Anthorn, United Kingdom", latitude: 54.9117, longitude: -3.2785 },
+    VlfTransmitter { callsign: "NWC", frequency: 19800, location: "North West Cape, Australia", latitude: -21.816328, longitude: 114.165586 },
+    VlfTransmitter { callsign: "ICV", frequency: 20270, location: "Tavolara, Italy", latitude: 40.9231, longitude: 9.7310 },
+    VlfTransmitter { callsign: "FTA", frequency: 20900, location: "Sainte-Assise, France", latitude: 48.5447, longitude: 2.5792 },
+    VlfTransmitter { callsign: "NPM", frequency: 21400, location: "Lualualei, Hawaii", latitude: 21.4202, longitude: -158.1512 },
+    VlfTransmitter { callsign: "GQD", frequency: 22100, location: "Anthorn, United Kingdom", latitude: 54.9117, longitude: -3.2785 },
+    VlfTransmitter { callsign: "JJI", frequency: 22200, location: "Ebino, Japan", latitude: 32.0921, longitude: 130.8289 },
+    VlfTransmitter { callsign: "DHO38", frequency: 23400, location: "Rhauderfehn, Germany", latitude: 53.0789, longitude: 7.6150 },
+    VlfTransmitter { callsign: "NAA", frequency: 24000, location: "Cutler, Maine, USA", latitude: 44.6449, longitude: -67.2816 },
+    VlfTransmitter { callsign: "NLK", frequency: 24800, location: "Jim Creek, Washington, USA", latitude: 48.2036, longitude: -121.9171 },
+    VlfTransmitter { callsign: "NML", frequency: 25200, location: "LaMoure, North Dakota, USA", latitude: 46.3660, longitude: -98.3357 },
+    VlfTransmitter { callsign: "TBB", frequency: 26700, location: "Bafa, Turkey", latitude: 37.4127, longitude: 27.3233 },
+    VlfTransmitter { callsign: "NRK", frequency: 37500, location: "Grindavik, Iceland", latitude: 63.8508, longitude: -22.4667 },
+    VlfTransmitter { callsign: "NAU", frequency: 40750, location: "Aguada, Puerto Rico", latitude: 18.3987, longitude: -67.1774 },
+    VlfTransmitter { callsign: "NSY", frequency: 45900, location: "Niscemi, Italy", latitude: 37.1256, longitude: 14.4363 },
+    VlfTransmitter { callsign: "JXN", frequency: 16400, location: "Noviken, Norway", latitude: 66.9823, longitude: 13.8762 },
+];
+
+/// Returns the transmitters below `max_frequency`, nearest first, with their distance in km.
+pub fn nearest(latitude: f64, longitude: f64, max_frequency: usize) -> Vec<(VlfTransmitter, f64)> {
+    let mut transmitters: Vec<(VlfTransmitter, f64)> = VLF_TRANSMITTERS.iter()
+        .filter(|transmitter| transmitter.frequency <= max_frequency)
+        .map(|transmitter| (*transmitter, transmitter.distance_km(latitude, longitude)))
+        .collect();
+    transmitters.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+    transmitters
+}
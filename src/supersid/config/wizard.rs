@@ -0,0 +1,298 @@
+//! Terminal wizard creating a configuration for a new site.
+
+use std::io::{BufRead, Write};
+use super::{ConfigError, FtpConfig, LogFormat, LogType, SampleIntegrationAlgorithm, StationChannel, StationConfig, SuperSidConfig, SuperSidSite};
+use crate::sound_card::config::{Format, SamplingRate, SoundCardConfig};
+
+const SUGGESTED_STATIONS: usize = 10;
+
+struct Prompter<R: BufRead, W: Write> {
+    input: R,
+    output: W
+}
+
+impl<R: BufRead, W: Write> Prompter<R, W> {
+    fn say(&mut self, text: &str) -> Result<(), ConfigError> {
+        writeln!(self.output, "{}", text)?;
+        Ok(())
+    }
+
+    /// Asks a question and returns the trimmed answer, or `default` when the answer is empty.
+    fn ask(&mut self, question: &str, default: Option<&str>) -> Result<String, ConfigError> {
+        loop {
+            match default {
+                Some(default) => write!(self.output, "{} [{}]: ", question, default)?,
+                None => write!(self.output, "{}: ", question)?
+            };
+            self.output.flush()?;
+
+            let mut answer = String::new();
+            if self.input.read_line(&mut answer)? == 0 {
+                return Err(ConfigError::Io(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "input closed before the configuration was complete")));
+            }
+
+            let answer = answer.trim();
+            if !answer.is_empty() {
+                return Ok(answer.to_string());
+            }
+            if let Some(default) = default {
+                return Ok(default.to_string());
+            }
+        }
+    }
+
+    /// Asks until the answer parses and passes `check`, which returns the reason for rejecting it.
+    fn ask_parsed<V: std::str::FromStr>(&mut self, question: &str, default: Option<&str>, check: &dyn Fn(&V) -> Option<String>) -> Result<V, ConfigError> {
+        loop {
+            let answer = self.ask(question, default)?;
+            match answer.parse::<V>() {
+                Ok(value) => match check(&value) {
+                    Some(reason) => self.say(&format!("  {}", reason))?,
+                    None => return Ok(value)
+                },
+                Err(_) => self.say(&format!("  '{}' is not a valid value", answer))?
+            };
+        }
+    }
+
+    /// Asks to pick one of `count` numbered entries, returning its index.
+    fn choose(&mut self, question: &str, count: usize, default: usize) -> Result<usize, ConfigError> {
+        let number = self.ask_parsed::<usize>(question, Some(&(default + 1).to_string()), &|number| {
+            if *number >= 1 && *number <= count { None } else { Some(format!("pick a number between 1 and {}", count)) }
+        })?;
+        Ok(number - 1)
+    }
+}
+
+impl SuperSidConfig {
+    /// Walks a new volunteer through the configuration of a site and saves the result to the
+    /// path they choose (`.toml` or `.json`).
+    pub fn prompt_new<R: BufRead, W: Write>(input: R, output: W) -> Result<Self, ConfigError> {
+        let devices = crate::sound_card::alsa::discover_capture_devices().unwrap_or_default();
+        Self::prompt_with_devices(input, output, devices)
+    }
+
+    /// Runs the wizard offering `devices` as the capture devices found.
+    fn prompt_with_devices<R: BufRead, W: Write>(input: R, output: W, mut devices: Vec<crate::sound_card::alsa::CaptureDevice>) -> Result<Self, ConfigError> {
+        let mut prompter = Prompter { input, output };
+
+        prompter.say("SuperSID configuration\n\n-- Site --")?;
+        let site_name = prompter.ask("Site name", None)?;
+        let contact = prompter.ask("Contact email", None)?;
+        let latitude = prompter.ask_parsed::<f64>("Latitude in decimal degrees", None, &|latitude| {
+            if (-90f64..=90f64).contains(latitude) { None } else { Some("latitude must be within -90 and 90".to_string()) }
+        })?;
+        let longitude = prompter.ask_parsed::<f64>("Longitude in decimal degrees", None, &|longitude| {
+            if (-180f64..=180f64).contains(longitude) { None } else { Some("longitude must be within -180 and 180".to_string()) }
+        })?;
+        let utc_offset = prompter.ask("UTC offset", Some("+00:00"))?;
+        let time_zone = prompter.ask("Time zone", Some("UTC"))?;
+        let monitor_id = prompter.ask("Monitor id", Some(&site_name))?;
+
+        prompter.say("\n-- Sound card --")?;
+        let (device_id, capabilities) = if devices.is_empty() {
+            prompter.say("No ALSA capture device found.")?;
+            let device_id = prompter.ask("Device name", Some(super::legacy::DEFAULT_DEVICE))?;
+            let capabilities = crate::sound_card::alsa::probe_capture(&device_id);
+            (device_id, capabilities)
+        } else {
+            let mut i = 0usize;
+            while i < devices.len() {
+                let device = &devices[i];
+                prompter.say(&format!("{:3}) {} - {}", i + 1, device.name, device.description))?;
+                match &device.capabilities {
+                    Ok(capabilities) => prompter.say(&format!("       {}", capabilities))?,
+                    Err(error) => prompter.say(&format!("       {}", error))?
+                };
+                i += 1;
+            }
+            let default = devices.iter().position(|device| device.name.starts_with("plughw:") && device.capabilities.is_ok()).unwrap_or(0);
+            let device = devices.swap_remove(prompter.choose("Capture device", devices.len(), default)?);
+            (device.name, device.capabilities)
+        };
+
+        let channels = prompter.ask_parsed::<usize>("Channels to capture", Some("1"), &|channels| {
+            if *channels >= 1 { None } else { Some("at least one channel must be captured".to_string()) }
+        })?;
+
+        let mut combinations = match &capabilities {
+            Ok(capabilities) => capabilities.combinations(channels),
+            Err(_) => Vec::new()
+        };
+        if combinations.is_empty() {
+            match &capabilities {
+                Ok(capabilities) => prompter.say(&format!("'{}' does not capture {} channel(s) at a usual rate ({}), all combinations are offered untested.", device_id, channels, capabilities))?,
+                Err(error) => prompter.say(&format!("{}, all combinations are offered untested.", error))?
+            };
+            for sampling_rate in SamplingRate::COMMON.iter() {
+                for format in Format::ALL.iter() {
+                    combinations.push((*sampling_rate, *format));
+                }
+            }
+        }
+        let mut i = 0usize;
+        while i < combinations.len() {
+            let (sampling_rate, format) = combinations[i];
+            prompter.say(&format!("{:3}) {} Hz {}", i + 1, sampling_rate.value(), format.label()))?;
+            i += 1;
+        }
+        let (sampling_rate, format) = combinations[prompter.choose("Sampling rate and format", combinations.len(), 0)?];
+        let period_size = super::legacy::DEFAULT_PERIOD_SIZE;
+
+        prompter.say("\n-- Stations --")?;
+        let nyquist = sampling_rate.value() / 2;
+        let suggestions = super::transmitters::nearest(latitude, longitude, nyquist);
+        let mut i = 0usize;
+        while i < suggestions.len() && i < SUGGESTED_STATIONS {
+            let (transmitter, distance) = &suggestions[i];
+            prompter.say(&format!("{:3}) {:6} {:6} Hz {:6.0} km  {}", i + 1, transmitter.callsign, transmitter.frequency, distance, transmitter.location))?;
+            i += 1;
+        }
+
+        let mut stations = Vec::<StationConfig>::new();
+        while stations.is_empty() {
+            let answer = prompter.ask("Stations to monitor (numbers above or CALLSIGN:FREQUENCY, comma separated)", Some("1,2,3"))?;
+            for item in answer.split(',').map(|item| item.trim()).filter(|item| !item.is_empty()) {
+                let color = StationConfig::COLORS[stations.len() % (StationConfig::COLORS.len() - 1)];
+                let station = match item.split_once(':') {
+                    Some((callsign, frequency)) => match frequency.trim().parse::<usize>() {
+                        Ok(frequency) if frequency <= nyquist => Some(StationConfig::new(callsign.trim(), color, frequency)),
+                        _ => None
+                    },
+                    None => match item.parse::<usize>() {
+                        Ok(number) if number >= 1 && number <= std::cmp::min(suggestions.len(), SUGGESTED_STATIONS) => {
+                            let (transmitter, _) = &suggestions[number - 1];
+                            Some(StationConfig::new(transmitter.callsign, color, transmitter.frequency))
+                        },
+                        _ => None
+                    }
+                };
+                match station {
+                    Some(station) => stations.push(station),
+                    None => {
+                        prompter.say(&format!("  '{}' is not a listed number nor a CALLSIGN:FREQUENCY below {} Hz", item, nyquist))?;
+                        stations.clear();
+                        break;
+                    }
+                };
+            }
+        }
+
+        if channels > 1 {
+            for station in stations.iter_mut() {
+                loop {
+                    let answer = prompter.ask(&format!("Channel of {} (0 to {}, sum or max)", station.callsign, channels - 1), Some("0"))?;
+                    match StationChannel::from_label(&answer) {
+                        Some(StationChannel::Index(index)) if index >= channels => prompter.say(&format!("  only {} channel(s) are captured", channels))?,
+                        Some(channel) => {
+                            station.channel = channel;
+                            break;
+                        },
+                        None => prompter.say(&format!("  '{}' is not a channel index, sum or max", answer))?
+                    };
+                }
+            }
+        }
+
+        prompter.say("\n-- Logging --")?;
+        let log_interval = prompter.ask_parsed::<usize>("Seconds between readings", Some("5"), &|interval| {
+            if *interval > 2 { None } else { Some("must be greater than 2 seconds".to_string()) }
+        })?;
+        let data_path = prompter.ask("Data directory", Some(super::legacy::DEFAULT_DATA_PATH))?;
+
+        let config = Self {
+            monitor_id,
+            site: SuperSidSite {
+                site_name,
+                site_contact_email: contact,
+                site_latitude: latitude,
+                site_longitude: longitude,
+                utc_offset,
+                time_zone
+            },
+            sound_card: SoundCardConfig::new(&device_id, format, sampling_rate, period_size),
+            channels,
+            stations,
+            sample_integration_algorithm: SampleIntegrationAlgorithm::OneChannel,
+            log_interval,
+            log_type: LogType::Filtered,
+            log_format: LogFormat::SupersidExtended,
+            scaling_factor: 1.0,
+            hourly_save: true,
+            bema_wing: super::legacy::DEFAULT_BEMA_WING,
+            data_path: std::path::PathBuf::from(data_path),
+            calibration: None,
+            ftp: FtpConfig::default()
+        };
+        config.validate()?;
+
+        loop {
+            let path = prompter.ask("\nSave configuration to", Some("supersid.toml"))?;
+            match config.save(&path) {
+                Ok(()) => {
+                    prompter.say(&format!("Configuration written to '{}'.", path))?;
+                    return Ok(config);
+                },
+                Err(error) => prompter.say(&format!("  {}", error))?
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prompts_a_new_configuration() {
+        let data_path = std::env::temp_dir().join(format!("supersid_wizard_data_{}", std::process::id()));
+        let path = std::env::temp_dir().join(format!("supersid_wizard_{}.toml", std::process::id()));
+        let combination = SamplingRate::COMMON.iter().position(|sampling_rate| *sampling_rate == SamplingRate::Hz48000).unwrap() * Format::ALL.len()
+            + Format::ALL.iter().position(|format| *format == Format::B16).unwrap() + 1;
+        let answers = [
+            "EXAMPLE",
+            "you@domain.tld",
+            "91",
+            "51.478",
+            "-0.001",
+            "",
+            "",
+            "SAMPLE1",
+            // No device is offered, and a missing one leaves every combination untested.
+            "supersid-missing-device",
+            "",
+            &combination.to_string(),
+            "1,NAA:24001",
+            "1, VTX3:18200",
+            "two",
+            "2",
+            "5",
+            data_path.to_str().unwrap(),
+            path.to_str().unwrap()
+        ].join("\n") + "\n";
+
+        let mut output = Vec::<u8>::new();
+        let config = SuperSidConfig::prompt_with_devices(answers.as_bytes(), &mut output, Vec::new()).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let loaded = SuperSidConfig::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(output.contains("latitude must be within -90 and 90"));
+        assert!(output.contains("'NAA:24001' is not a listed number nor a CALLSIGN:FREQUENCY below 24000 Hz"));
+        assert!(output.contains("'two' is not a valid value"));
+        assert!(output.contains("must be greater than 2 seconds"));
+
+        assert_eq!(config.monitor_id, "SAMPLE1");
+        assert_eq!((config.site.site_name.as_str(), config.site.site_contact_email.as_str()), ("EXAMPLE", "you@domain.tld"));
+        assert_eq!((config.site.site_latitude, config.site.site_longitude), (51.478, -0.001));
+        assert_eq!((config.site.utc_offset.as_str(), config.site.time_zone.as_str()), ("+00:00", "UTC"));
+        assert_eq!(config.sound_card.device_id, "supersid-missing-device");
+        assert_eq!((config.sound_card.sampling_rate, config.sound_card.format, config.channels), (SamplingRate::Hz48000, Format::B16, 1));
+        let (nearest, _) = super::super::transmitters::nearest(51.478, -0.001, 24000)[0];
+        let stations: Vec<(&str, usize, StationChannel)> = config.stations.iter().map(|station| (station.callsign.as_str(), station.frequency, station.channel)).collect();
+        assert_eq!(stations, vec![(nearest.callsign, nearest.frequency, StationChannel::Index(0)), ("VTX3", 18200, StationChannel::Index(0))]);
+        assert_eq!((config.log_interval, config.data_path.as_path()), (5, data_path.as_path()));
+
+        assert_eq!(serde_json::to_value(&loaded).unwrap(), serde_json::to_value(&config).unwrap());
+    }
+}
@@ -0,0 +1,361 @@
+//! Destinations of the station readings taken by [`super::SuperSid::run`].
+
+use super::config::{LogFormat, LogType, StationConfig, SuperSidConfig};
+use super::sidfile::{SidFile, SidHeader};
+use crate::spectral_density::{Measurement, SpectralDensitySample};
+
+/// Format of the timestamps written by the loggers, as in the Python SuperSID.
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+pub trait SuperSidLogger<U: Measurement> {
+    /// Receives the reading of each station, in the order of `stations`, taken at `timestamp`.
+    fn log(&mut self, timestamp: chrono::DateTime<chrono::Utc>, stations: &[StationConfig], station_data: &[SpectralDensitySample<U, U>]) -> Result<(), std::io::Error>;
+
+    /// Receives the start of a slot skipped because the previous reading overran it.
+    fn missed(&mut self, _scheduled: chrono::DateTime<chrono::Utc>) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+
+    /// Called once when monitoring stops.
+    fn close(&mut self) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+}
+
+/// Prints one status line per reading on the standard output.
+pub struct ConsoleLogger {
+    log_interval: usize
+}
+
+impl ConsoleLogger {
+    pub fn new(log_interval: usize) -> Self {
+        Self { log_interval }
+    }
+}
+
+impl<U: Measurement> SuperSidLogger<U> for ConsoleLogger {
+    fn log(&mut self, timestamp: chrono::DateTime<chrono::Utc>, stations: &[StationConfig], station_data: &[SpectralDensitySample<U, U>]) -> Result<(), std::io::Error> {
+        use chrono::Timelike;
+        use std::io::Write;
+
+        let index = timestamp.num_seconds_from_midnight() as usize / self.log_interval;
+        let readings: Vec<String> = stations.iter().zip(station_data.iter())
+            .map(|(station, sample)| format!("{}={:.6e}", station.callsign, sample.spectral_density().to_f64().unwrap()))
+            .collect();
+
+        let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "{} [{}] {}", timestamp.format(TIMESTAMP_FORMAT), index, readings.join(" "))?;
+        stdout.flush()
+    }
+
+    fn missed(&mut self, scheduled: chrono::DateTime<chrono::Utc>) -> Result<(), std::io::Error> {
+        use chrono::Timelike;
+        let index = scheduled.num_seconds_from_midnight() as usize / self.log_interval;
+        eprintln!("{} [{}] missed, the previous reading overran", scheduled.format(TIMESTAMP_FORMAT), index);
+        Ok(())
+    }
+}
+
+/// Run by [`SidFileLogger`] once a UTC day is over, with the files written for it.
+pub trait PostDayHook {
+    fn run(&mut self, sid_file: &SidFile, paths: &[std::path::PathBuf]) -> Result<(), std::io::Error>;
+}
+
+/// Runs a shell command with the paths of the day's files as arguments, for instance an upload
+/// script. The command runs in the background so the next reading is not delayed.
+pub struct CommandHook {
+    command: String
+}
+
+impl CommandHook {
+    pub fn new(command: &str) -> Self {
+        Self { command: command.to_string() }
+    }
+}
+
+impl PostDayHook for CommandHook {
+    fn run(&mut self, _sid_file: &SidFile, paths: &[std::path::PathBuf]) -> Result<(), std::io::Error> {
+        let mut child = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$@\"", self.command))
+            .arg("sh")
+            .args(paths)
+            .spawn()?;
+        std::thread::spawn(move || child.wait());
+        Ok(())
+    }
+}
+
+/// Keeps the readings of the current UTC day in a [`SidFile`] and writes it in `data_path` with
+/// the configured `log_format` and `log_type` when the day ends and when monitoring stops.
+///
+/// Slots skipped by an overrun are written as NaN, which the filtered files leave as gaps.
+///
+/// With `hourly_save`, the raw readings are also checkpointed every hour to
+/// `hourly_current_buffers.raw.ext.<date>.csv`, which [`SidFileLogger::resume`] picks up after a
+/// power loss.
+pub struct SidFileLogger {
+    sid_file: SidFile,
+    data_path: std::path::PathBuf,
+    log_format: LogFormat,
+    log_type: LogType,
+    bema_wing: usize,
+    hourly_save: bool,
+    checkpoint_hour: Option<(chrono::NaiveDate, u32)>,
+    /// Readings of a later day, kept until the current day is written.
+    pending: Vec<(chrono::NaiveDateTime, Vec<f64>)>,
+    hooks: Vec<Box<dyn PostDayHook>>
+}
+
+impl SidFileLogger {
+    pub fn new(config: &SuperSidConfig, date: chrono::NaiveDate) -> Self {
+        Self {
+            sid_file: SidFile::new(SidHeader::from_config(config, date)),
+            data_path: config.data_path.clone(),
+            log_format: config.log_format,
+            log_type: config.log_type,
+            bema_wing: config.bema_wing,
+            hourly_save: config.hourly_save,
+            checkpoint_hour: None,
+            pending: Vec::new(),
+            hooks: Vec::new()
+        }
+    }
+
+    /// Adds a hook run after the files of a day are written at UTC midnight.
+    pub fn add_hook(&mut self, hook: Box<dyn PostDayHook>) {
+        self.hooks.push(hook);
+    }
+
+    /// Reloads the readings of the current day already written in `data_path` by this monitor,
+    /// so a restart does not lose them. Files of other days or monitors are left alone; files
+    /// whose stations or interval do not match the configuration are reported and skipped, as
+    /// are filtered files since their readings would be filtered again when written. The most
+    /// recent raw files win. Returns the files loaded.
+    pub fn resume(&mut self) -> Result<Vec<std::path::PathBuf>, std::io::Error> {
+        let entries = match std::fs::read_dir(&self.data_path) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error)
+        };
+
+        let header = &self.sid_file.header;
+        let mut candidates = Vec::<(std::time::SystemTime, std::path::PathBuf, SidFile)>::new();
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("csv") {
+                continue;
+            }
+            // Other CSV files (recordings, unrelated data) are not SID files and are ignored.
+            let sid_file = match SidFile::from_file(&path) {
+                Ok(sid_file) => sid_file,
+                Err(_) => continue
+            };
+            if sid_file.header.monitor_id != header.monitor_id || sid_file.header.utc_start_time != header.utc_start_time {
+                continue;
+            }
+
+            let mut stations = sid_file.header.stations.clone();
+            stations.sort();
+            let mut expected = header.stations.clone();
+            expected.sort();
+            let stations_match = if sid_file.is_supersid { stations == expected } else { stations.iter().all(|station| expected.contains(station)) };
+            if !stations_match {
+                eprintln!("Not resuming from '{}': stations {} differ from the configured {}.", path.display(), sid_file.header.stations.join(","), header.stations.join(","));
+                continue;
+            }
+            if sid_file.header.log_interval != header.log_interval {
+                eprintln!("Not resuming from '{}': LogInterval = {} differs from the configured {}.", path.display(), sid_file.header.log_interval, header.log_interval);
+                continue;
+            }
+            if sid_file.header.log_type != LogType::Raw {
+                eprintln!("Not resuming from '{}': its readings are filtered, only raw files and hourly checkpoints can be resumed.", path.display());
+                continue;
+            }
+
+            let modified = entry.metadata()?.modified()?;
+            candidates.push((modified, path, sid_file));
+        }
+
+        // Copy the oldest files first, so the most recent ones overwrite them.
+        candidates.sort_by_key(|(modified, _, _)| *modified);
+        let mut loaded = Vec::<std::path::PathBuf>::new();
+        for (_, path, sid_file) in candidates {
+            if !self.sid_file.copy_data(&sid_file).is_empty() {
+                loaded.push(path);
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// Writes the buffers of the current day and returns the paths of the files.
+    pub fn save(&self) -> Result<Vec<std::path::PathBuf>, std::io::Error> {
+        self.sid_file.save(&self.data_path, self.log_format, self.log_type, self.bema_wing, None)
+    }
+
+    /// Writes the raw buffers of the current day to the hourly checkpoint file.
+    pub fn checkpoint(&self) -> Result<std::path::PathBuf, std::io::Error> {
+        let filename = format!("hourly_current_buffers.raw.ext.{}.csv", self.sid_file.header.utc_start_time.format("%Y-%m-%d"));
+        let paths = self.sid_file.save(&self.data_path, LogFormat::SupersidExtended, LogType::Raw, self.bema_wing, Some(&filename))?;
+        Ok(paths[0].clone())
+    }
+
+    /// Closes the current day: last checkpoint, day files, hooks, then empty buffers for `date`.
+    /// The buffers are kept when the files cannot be written, so the next reading retries.
+    fn rotate(&mut self, date: chrono::NaiveDate) -> Result<(), std::io::Error> {
+        if self.hourly_save {
+            self.checkpoint()?;
+        }
+        let paths = self.save()?;
+        for path in paths.iter() {
+            eprintln!("Data written to '{}'.", path.display());
+        }
+        for hook in self.hooks.iter_mut() {
+            if let Err(error) = hook.run(&self.sid_file, &paths) {
+                eprintln!("Post-day hook failed: {}", error);
+            }
+        }
+        self.sid_file.clear(date);
+        Ok(())
+    }
+
+    /// Stores readings in their slot, rotating first when they start a new day. When the day
+    /// cannot be written, the readings wait and the next ones retry the rotation.
+    fn store(&mut self, timestamp: chrono::NaiveDateTime, values: Vec<f64>) -> Result<(), std::io::Error> {
+        self.pending.push((timestamp, values));
+        while let Some(date) = self.pending.iter().map(|(timestamp, _)| timestamp.date()).min() {
+            if date > self.sid_file.header.utc_start_time.date() {
+                self.rotate(date)?;
+            }
+            let (stored, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending).into_iter().partition(|(timestamp, _)| timestamp.date() <= date);
+            self.pending = pending;
+            for (timestamp, values) in stored.iter() {
+                self.sid_file.set_readings(*timestamp, values);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<U: Measurement> SuperSidLogger<U> for SidFileLogger {
+    fn log(&mut self, timestamp: chrono::DateTime<chrono::Utc>, _stations: &[StationConfig], station_data: &[SpectralDensitySample<U, U>]) -> Result<(), std::io::Error> {
+        use chrono::Timelike;
+
+        let timestamp = timestamp.naive_utc();
+        let values: Vec<f64> = station_data.iter().map(|sample| sample.spectral_density().to_f64().unwrap()).collect();
+        self.store(timestamp, values)?;
+
+        // The first reading of each hour triggers the checkpoint, so it includes that reading.
+        let hour = (timestamp.date(), timestamp.hour());
+        if self.hourly_save && self.checkpoint_hour != Some(hour) {
+            if self.checkpoint_hour.is_some() {
+                self.checkpoint()?;
+            }
+            self.checkpoint_hour = Some(hour);
+        }
+        Ok(())
+    }
+
+    fn missed(&mut self, scheduled: chrono::DateTime<chrono::Utc>) -> Result<(), std::io::Error> {
+        let gap = vec![f64::NAN; self.sid_file.data.len()];
+        self.store(scheduled.naive_utc(), gap)
+    }
+
+    fn close(&mut self) -> Result<(), std::io::Error> {
+        for path in self.save()? {
+            eprintln!("Data written to '{}'.", path.display());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_directory(name: &str) -> std::path::PathBuf {
+        let directory = std::env::temp_dir().join(format!("supersid-logger-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        directory
+    }
+
+    fn test_config(data_path: &std::path::Path) -> SuperSidConfig {
+        let stations = vec![StationConfig::new("NWC", 'r', 19800), StationConfig::new("VTX3", 'b', 18200)];
+        SuperSidConfig::for_tests("synthetic:19800", 1, stations, data_path)
+    }
+
+    #[test]
+    fn resume_skips_filtered_files() {
+        let directory = test_directory("resume");
+        let config = test_config(&directory);
+        let date = chrono::NaiveDate::from_ymd_opt(2026, 10, 18).unwrap();
+        let noon = date.and_hms_opt(12, 0, 0).unwrap();
+
+        let mut written = SidFile::new(SidHeader::from_config(&config, date));
+        written.set_readings(noon, &[1., 2.]);
+        written.save(&directory, LogFormat::SupersidExtended, LogType::Filtered, config.bema_wing, None).unwrap();
+
+        let mut logger = SidFileLogger::new(&config, date);
+        assert!(logger.resume().unwrap().is_empty());
+
+        let raw = written.save(&directory, LogFormat::SupersidExtended, LogType::Raw, config.bema_wing, None).unwrap();
+        let mut logger = SidFileLogger::new(&config, date);
+        assert_eq!(logger.resume().unwrap(), raw);
+        let slot = logger.sid_file.slot(noon).unwrap();
+        assert_eq!((logger.sid_file.data[0][slot], logger.sid_file.data[1][slot]), (1., 2.));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    fn log(logger: &mut SidFileLogger, stations: &[StationConfig], timestamp: chrono::NaiveDateTime, values: &[f64]) -> Result<(), std::io::Error> {
+        let samples: Vec<SpectralDensitySample<f64, f64>> = stations.iter().zip(values.iter())
+            .map(|(station, value)| SpectralDensitySample::new(station.frequency as f64, *value))
+            .collect();
+        SuperSidLogger::<f64>::log(logger, timestamp.and_utc(), stations, &samples)
+    }
+
+    #[test]
+    fn missed_slots_are_gaps() {
+        let directory = test_directory("missed");
+        let config = test_config(&directory);
+        let date = chrono::NaiveDate::from_ymd_opt(2026, 10, 18).unwrap();
+        let mut logger = SidFileLogger::new(&config, date);
+
+        let missed = date.and_hms_opt(12, 0, 5).unwrap();
+        SuperSidLogger::<f64>::missed(&mut logger, missed.and_utc()).unwrap();
+        log(&mut logger, &config.stations, date.and_hms_opt(12, 0, 10).unwrap(), &[1., 2.]).unwrap();
+
+        let slot = logger.sid_file.slot(missed).unwrap();
+        assert!(logger.sid_file.data.iter().all(|series| series[slot].is_nan()));
+        assert_eq!((logger.sid_file.data[0][slot + 1], logger.sid_file.data[1][slot + 1]), (1., 2.));
+    }
+
+    #[test]
+    fn failed_rotation_keeps_the_readings() {
+        let directory = test_directory("rotation");
+        // A file where the data directory should be makes the day files fail.
+        let data_path = directory.join("data");
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(&data_path, "").unwrap();
+        let config = test_config(&data_path);
+        let date = chrono::NaiveDate::from_ymd_opt(2026, 10, 18).unwrap();
+        let next_date = date.succ_opt().unwrap();
+        let mut logger = SidFileLogger::new(&config, date);
+
+        let last = date.and_hms_opt(23, 59, 55).unwrap();
+        log(&mut logger, &config.stations, last, &[1., 2.]).unwrap();
+        let first = next_date.and_hms_opt(0, 0, 0).unwrap();
+        assert!(log(&mut logger, &config.stations, first, &[3., 4.]).is_err());
+        assert_eq!(logger.sid_file.header.utc_start_time.date(), date);
+
+        std::fs::remove_file(&data_path).unwrap();
+        let second = next_date.and_hms_opt(0, 0, 5).unwrap();
+        log(&mut logger, &config.stations, second, &[5., 6.]).unwrap();
+        assert_eq!(logger.sid_file.header.utc_start_time.date(), next_date);
+        assert_eq!(logger.sid_file.data[0][..2], [3., 5.]);
+
+        let written = SidFile::from_file(data_path.join("TESTSITE_2026-10-18.csv")).unwrap();
+        assert_eq!(written.data[1][written.data[1].len() - 1], 2.);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -1,6 +1,10 @@
 use num_traits::ToPrimitive;
-use crate::sound_card::{{SoundCard, SoundCardRecorder}};
+use crate::sound_card::SoundCardRecorder;
+pub mod calibration;
 pub mod config;
+pub mod logger;
+pub mod scheduler;
+pub mod sidfile;
 
 /// Returns N for the window of the welch spectral density
 #[allow(non_snake_case)]
@@ -17,55 +21,307 @@ pub struct SuperSid<'a, T: crate::math::Sample, U: crate::spectral_density::Meas
     pub config: &'a config::SuperSidConfig,
     pub raw_data: Vec<crate::sound_card::ChannelData<T>>,
     pub spectrum: Vec<crate::spectral_density::SpectralDensity<U>>,
-    pub station_data: Vec<super::spectral_density::SpectralDensitySample<U, U>>
+    pub station_data: Vec<super::spectral_density::SpectralDensitySample<U, U>>,
+    /// UTC time of the first frame `station_data` was measured from, when the sound card reports it.
+    pub reading_time: Option<chrono::DateTime<chrono::Utc>>,
+    /// Frames the sound card lost to overruns while recording `raw_data`.
+    pub dropped_frames: usize,
+    /// Correction of the sound card frequency response applied to `station_data`.
+    pub calibration: Option<calibration::Calibration>,
+    /// Second recorded by the previous reading, integrated again by `TwoChannel`.
+    previous_data: Vec<crate::sound_card::ChannelData<T>>,
+    /// Channel whose last two seconds make the next `TwoChannel` reading.
+    integrated_channel: usize,
+    recorder: Box<dyn SoundCardRecorder<T> + 'a>
 }
 
 
 impl<'a, T: crate::math::Sample, U: crate::spectral_density::Measurement> SuperSid<'a, T, U> {
-    pub fn measure(config: &'a config::SuperSidConfig) {
-        
-        let sampling_rate_f64 = config.sound_card.sampling_rate.sample_value::<f64>();
+    /// Milliseconds recorded for each reading.
+    pub const CAPTURE_MILLISECONDS: usize = 1000;
 
-        let n: usize = 256;
-        
-        let sound_card = crate::sound_card::alsa::AlsaSoundCard::<f64>::new(config.sound_card.clone());
-        let mut recorder = sound_card.create_alsa_recorder(2);
-        let data: Vec<crate::sound_card::ChannelData<f64>>;
+    pub fn new(config: &'a config::SuperSidConfig, recorder: Box<dyn SoundCardRecorder<T> + 'a>) -> Self {
+        Self {
+            config,
+            raw_data: Vec::with_capacity(config.channels),
+            spectrum: Vec::with_capacity(config.channels),
+            station_data: Vec::with_capacity(config.stations.len()),
+            reading_time: None,
+            dropped_frames: 0,
+            calibration: None,
+            previous_data: Vec::with_capacity(config.channels),
+            integrated_channel: 0,
+            recorder
+        }
+    }
+
+    pub fn with_calibration(mut self, calibration: calibration::Calibration) -> Self {
+        self.calibration = Some(calibration);
+        self
+    }
 
-        let start = std::time::Instant::now();
+    /// Records one second, then fills `spectrum` and `station_data` with the reading of each
+    /// station, corrected by `calibration` when there is one.
+    ///
+    /// `OneChannel` fills `spectrum` with the density of each channel over that second.
+    /// `TwoChannel` expects one antenna wired to both inputs and fills `spectrum` with a single
+    /// density over two seconds: the previous second and this one of a channel, the channels
+    /// taking turns, so that each channel is integrated over two seconds and a reading still
+    /// comes every second. A failed reading starts over from one second.
+    pub fn measure(&mut self) -> Result<(), crate::sound_card::SoundCardError> {
+        let sampling_rate = U::from_usize(self.config.sound_card.sampling_rate.value()).unwrap();
+        let format = self.config.sound_card.format;
+        let scale = |sample: &T| format.normalize(sample.to_f64().unwrap()) * self.config.scaling_factor;
 
-        match recorder.record(1000) {
-            Ok(res_data) => data = res_data,
-            Err(error) => panic!("Unable to record: {}", error)
+        self.raw_data = match self.recorder.record(Self::CAPTURE_MILLISECONDS) {
+            Ok(data) => data,
+            Err(error) => {
+                self.previous_data.clear();
+                return Err(error);
+            }
         };
+        if self.raw_data.is_empty() {
+            return Err(crate::sound_card::SoundCardError::UnsupportedChannels { device: self.config.sound_card.device_id.clone(), channels: 0 });
+        }
 
-        let record_finish_time = std::time::Instant::now();
+        self.spectrum.clear();
+        match self.config.sample_integration_algorithm {
+            config::SampleIntegrationAlgorithm::OneChannel => {
+                for channel in self.raw_data.iter() {
+                    let window: Vec<f64> = channel.channel_data.iter().map(scale).collect();
+                    self.spectrum.push(crate::spectral_density::SpectralDensity::<U>::new::<f64>(&window, sampling_rate, get_N(sampling_rate)).with_record_times(channel.record_start, channel.record_end));
+                }
+            },
+            config::SampleIntegrationAlgorithm::TwoChannel => {
+                let i = self.integrated_channel % self.raw_data.len();
+                let channel = &self.raw_data[i];
+                let mut window = Vec::<f64>::with_capacity(channel.channel_data.len() * 2);
+                let mut record_start = channel.record_start;
+                let mut seconds = 1usize;
+                if let Some(previous) = self.previous_data.get(i) {
+                    window.extend(previous.channel_data.iter().map(scale));
+                    record_start = previous.record_start;
+                    seconds = 2;
+                }
+                window.extend(channel.channel_data.iter().map(scale));
+                // The Welch segments keep their length, there are twice as many of them.
+                self.spectrum.push(crate::spectral_density::SpectralDensity::<U>::new::<f64>(&window, sampling_rate, get_N(sampling_rate) * seconds).with_record_times(record_start, channel.record_end));
+                self.integrated_channel = (i + 1) % self.raw_data.len();
+                self.previous_data = self.raw_data.clone();
+            }
+        }
 
-        let mut spec_density = Vec::<crate::spectral_density::SpectralDensity::<f64>>::new(); 
-        let mut i = 0usize;
-        while i < data.len() {
-            spec_density.push(crate::spectral_density::SpectralDensity::<f64>::new(&data[i].channel_data, sampling_rate_f64, n));
-            i += 1;
+        // The second recorded by this reading dates it, also when the previous one is integrated.
+        self.reading_time = self.raw_data.iter().filter_map(|channel| channel.record_start).min();
+        self.dropped_frames = self.raw_data.iter().map(|channel| channel.dropped_frames).max().unwrap_or(0);
+        self.station_data.clear();
+        for station in self.config.stations.iter() {
+            let sample = match station.read(&self.spectrum) {
+                Some(sample) => match &self.calibration {
+                    Some(calibration) => calibration.correct(sample),
+                    None => sample
+                },
+                None => crate::spectral_density::SpectralDensitySample::<U, U>::new(U::from_usize(station.frequency).unwrap(), U::nan())
+            };
+            self.station_data.push(sample);
         }
 
-        i = 0;
-        
-        let sd_finish_time = std::time::Instant::now();
+        Ok(())
+    }
 
-        while i < spec_density.len() {
-            crate::spectral_density::plotter::plot_spectrum::<f64>(&spec_density[i], format!("spectral_density_channel_{}.png", i+1), None, Some("Frequency [Hz]"), Some("Spectral density [s^2/Hz]"));
-            i += 1;
-        }
+    /// Measures every `log_interval` seconds, on boundaries aligned to UTC, and hands each set of
+    /// station readings to the loggers until `stop` is set.
+    pub fn run(&mut self, loggers: &mut [Box<dyn logger::SuperSidLogger<U> + 'a>], stop: &std::sync::atomic::AtomicBool) -> Result<(), std::io::Error> {
+        let mut scheduler = scheduler::Scheduler::new(scheduler::SystemClock, self.config.log_interval);
+        self.run_with(&mut scheduler, loggers, stop)
+    }
 
-        let plot_finish_time = std::time::Instant::now();
+    /// Same as [`SuperSid::run`] with the slots of `scheduler`. A reading that cannot be taken,
+    /// for instance while the sound card is unplugged, is reported and logged as NaN so the gap
+    /// shows in the data; the loop keeps going until the recorder runs out of samples, as one
+    /// reading a file does.
+    pub fn run_with<C: scheduler::Clock>(&mut self, scheduler: &mut scheduler::Scheduler<C>, loggers: &mut [Box<dyn logger::SuperSidLogger<U> + 'a>], stop: &std::sync::atomic::AtomicBool) -> Result<(), std::io::Error> {
+        while let Some(slot) = scheduler.wait(stop) {
+            for missed in slot.missed.iter() {
+                for logger in loggers.iter_mut() {
+                    if let Err(error) = logger.missed(*missed) {
+                        eprintln!("{} Logging failed: {}", missed.format(logger::TIMESTAMP_FORMAT), error);
+                    }
+                }
+            }
 
-        let record_elapsed = record_finish_time.duration_since(start).as_nanos().to_f64().unwrap() / 1000f64 / 1000f64;
-        let spectral_density_elapsed = sd_finish_time.duration_since(record_finish_time).as_nanos().to_f64().unwrap() / 1000f64 / 1000f64;
-        let plot_elapsed = plot_finish_time.duration_since(sd_finish_time).as_nanos().to_f64().unwrap() / 1000f64 / 1000f64;
+            let timestamp = match self.measure() {
+                Ok(()) => {
+                    if self.dropped_frames > 0 {
+                        eprintln!("{} {} frame(s) lost to sound card overruns", slot.timestamp.format(logger::TIMESTAMP_FORMAT), self.dropped_frames);
+                    }
+                    // The first frame dates the reading, unless the sound card clock disagrees with the slot.
+                    match self.reading_time {
+                        Some(time) if time >= slot.scheduled => time,
+                        _ => slot.timestamp
+                    }
+                },
+                Err(crate::sound_card::SoundCardError::EndOfStream { .. }) => break,
+                Err(error) => {
+                    eprintln!("{} Reading failed: {}", slot.timestamp.format(logger::TIMESTAMP_FORMAT), error);
+                    self.station_data = self.config.stations.iter()
+                        .map(|station| crate::spectral_density::SpectralDensitySample::<U, U>::new(U::from_usize(station.frequency).unwrap(), U::nan()))
+                        .collect();
+                    slot.timestamp
+                }
+            };
 
-        println!("Recording duration: {} ms", record_elapsed);
-        println!("Spectral Density creation duration: {} ms", spectral_density_elapsed);
-        println!("Plot elapsed duration: {} ms", plot_elapsed);
+            for logger in loggers.iter_mut() {
+                if let Err(error) = logger.log(timestamp, &self.config.stations, &self.station_data) {
+                    eprintln!("{} Logging failed: {}", timestamp.format(logger::TIMESTAMP_FORMAT), error);
+                }
+            }
+        }
+
+        for logger in loggers.iter_mut() {
+            logger.close()?;
+        }
+        Ok(())
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::config::{SampleIntegrationAlgorithm, StationChannel, StationConfig, SuperSidConfig};
+    use crate::sound_card::SoundCard;
+
+    const TONE: usize = 19800;
+    /// Stations reading only noise, far enough below the tone for its leakage not to vary them.
+    const NOISE_FREQUENCIES: [usize; 15] = [2000, 3000, 4000, 5000, 6000, 7000, 8000, 9000, 10000, 11000, 12000, 13000, 14000, 15000, 16000];
+    const READINGS: usize = 40;
+
+    fn test_config(algorithm: SampleIntegrationAlgorithm, channels: usize) -> SuperSidConfig {
+        let mut stations = vec![StationConfig::new("NWC", 'r', TONE)];
+        stations.extend(NOISE_FREQUENCIES.iter().map(|frequency| StationConfig::new(&format!("N{}", frequency), 'b', *frequency)));
+        let mut config = SuperSidConfig::for_tests(&format!("synthetic:{}", TONE), channels, stations, std::path::Path::new("."));
+        config.sample_integration_algorithm = algorithm;
+        config
+    }
+
+    /// Records a pure tone of its own on each channel, the seconds dated one after the other.
+    struct ToneRecorder {
+        frequencies: Vec<f64>,
+        seconds: i64
+    }
+
+    impl SoundCardRecorder<i16> for ToneRecorder {
+        fn record(&mut self, milliseconds: usize) -> Result<Vec<crate::sound_card::ChannelData<i16>>, crate::sound_card::SoundCardError> {
+            let start = chrono::DateTime::<chrono::Utc>::UNIX_EPOCH + chrono::Duration::seconds(self.seconds);
+            self.seconds += 1;
+            Ok(self.frequencies.iter().enumerate().map(|(i, frequency)| crate::sound_card::ChannelData::<i16> {
+                record_start: Some(start),
+                record_end: Some(start + crate::sound_card::frames_duration(47999, 48000)),
+                ..crate::sound_card::ChannelData::<i16>::new(i + 1, crate::math::generate_tone::<i16>(*frequency, 48000., 48 * milliseconds, 10000.))
+            }).collect())
+        }
+
+        fn record_loop(&mut self, milliseconds: usize, each: &mut dyn FnMut(Vec<crate::sound_card::ChannelData<i16>>) -> bool) -> Result<(), crate::sound_card::SoundCardError> {
+            while each(self.record(milliseconds)?) {}
+            Ok(())
+        }
+    }
+
+    fn assert_peak(spectrum: &crate::spectral_density::SpectralDensity<f64>, frequency: f64) {
+        let peak = spectrum.peak.unwrap().frequency();
+        assert!((peak - frequency).abs() <= spectrum.freq_step, "peak at {} Hz instead of {} Hz", peak, frequency);
+    }
+
+    fn synthetic_recorder(config: &SuperSidConfig) -> Box<dyn SoundCardRecorder<i16>> {
+        let sound_card = crate::sound_card::synthetic::SyntheticSoundCard::<i16>::new(config.sound_card.clone()).unwrap()
+            .with_pacing(crate::sound_card::Pacing::AsFastAsPossible);
+        Box::new(sound_card.create_synthetic_recorder(config.channels).unwrap())
+    }
+
+    /// Returns the tone reading above the noise readings, in standard deviations of the noise.
+    fn signal_to_noise(algorithm: SampleIntegrationAlgorithm) -> f64 {
+        let config = test_config(algorithm, 2);
+        let mut supersid = SuperSid::<i16, f64>::new(&config, synthetic_recorder(&config));
+        supersid.measure().unwrap();
+
+        let mut tone = 0f64;
+        let mut noise: Vec<Vec<f64>> = vec![Vec::with_capacity(READINGS); NOISE_FREQUENCIES.len()];
+        for _ in 0..READINGS {
+            supersid.measure().unwrap();
+            tone += supersid.station_data[0].spectral_density() / READINGS as f64;
+            for (readings, sample) in noise.iter_mut().zip(supersid.station_data[1..].iter()) {
+                readings.push(sample.spectral_density());
+            }
+        }
+
+        let means: Vec<f64> = noise.iter().map(|readings| readings.iter().sum::<f64>() / READINGS as f64).collect();
+        let variance = noise.iter().zip(means.iter())
+            .map(|(readings, mean)| readings.iter().map(|reading| (reading - mean).powi(2)).sum::<f64>() / (READINGS - 1) as f64)
+            .sum::<f64>() / NOISE_FREQUENCIES.len() as f64;
+        let noise_mean = means.iter().sum::<f64>() / means.len() as f64;
+        (tone - noise_mean) / variance.sqrt()
+    }
+
+    #[test]
+    fn synthetic_tone_reads_above_the_noise_floor() {
+        let config = test_config(SampleIntegrationAlgorithm::OneChannel, 1);
+        let mut supersid = SuperSid::<i16, f64>::new(&config, synthetic_recorder(&config));
+        supersid.measure().unwrap();
+
+        let spectrum = &supersid.spectrum[0];
+        let tone = supersid.station_data[0];
+        assert!((tone.frequency() - TONE as f64).abs() <= spectrum.freq_step);
+        assert!(tone.spectral_density() > 10. * spectrum.noise_floor, "tone {}, floor {}", tone.spectral_density(), spectrum.noise_floor);
+        assert!(supersid.station_data[1..].iter().all(|sample| sample.spectral_density() < spectrum.noise_floor));
+    }
+
+    #[test]
+    fn two_channel_lowers_the_noise_of_the_readings() {
+        let one_channel = signal_to_noise(SampleIntegrationAlgorithm::OneChannel);
+        let two_channel = signal_to_noise(SampleIntegrationAlgorithm::TwoChannel);
+        // Twice the Welch segments should divide the deviation of the noise by about sqrt(2).
+        assert!(two_channel > 1.2 * one_channel, "OneChannel SNR {}, TwoChannel SNR {}", one_channel, two_channel);
+    }
+
+    #[test]
+    fn two_channel_alternates_the_channels() {
+        let config = test_config(SampleIntegrationAlgorithm::TwoChannel, 2);
+        let mut supersid = SuperSid::<i16, f64>::new(&config, Box::new(ToneRecorder { frequencies: vec![5000., 9000.], seconds: 0 }));
+        let n = get_N(48000f64);
+
+        supersid.measure().unwrap();
+        assert_eq!(supersid.raw_data.len(), 2);
+        assert_eq!(supersid.spectrum.len(), 1);
+        assert_eq!(supersid.spectrum[0].N, n);
+        assert_peak(&supersid.spectrum[0], 5000.);
+
+        // The second channel over the first two seconds, then the first one over the next two.
+        supersid.measure().unwrap();
+        assert_eq!(supersid.spectrum[0].N, 2 * n);
+        assert_peak(&supersid.spectrum[0], 9000.);
+        assert_eq!(supersid.spectrum[0].record_start, Some(chrono::DateTime::<chrono::Utc>::UNIX_EPOCH));
+        assert_eq!(supersid.reading_time, Some(chrono::DateTime::<chrono::Utc>::UNIX_EPOCH + chrono::Duration::seconds(1)));
+
+        supersid.measure().unwrap();
+        assert_eq!(supersid.spectrum[0].N, 2 * n);
+        assert_peak(&supersid.spectrum[0], 5000.);
+        assert_eq!(supersid.spectrum[0].record_start, Some(chrono::DateTime::<chrono::Utc>::UNIX_EPOCH + chrono::Duration::seconds(1)));
+    }
+
+    #[test]
+    fn one_channel_reads_each_channel_over_one_second() {
+        let mut config = test_config(SampleIntegrationAlgorithm::OneChannel, 2);
+        config.stations = vec![StationConfig::new("A", 'r', 5000), StationConfig::new("B", 'b', 9000), StationConfig::new("C", 'g', 9000)];
+        config.stations[1].channel = StationChannel::Index(1);
+        let mut supersid = SuperSid::<i16, f64>::new(&config, Box::new(ToneRecorder { frequencies: vec![5000., 9000.], seconds: 0 }));
+
+        supersid.measure().unwrap();
+        supersid.measure().unwrap();
+        assert_eq!(supersid.spectrum.len(), 2);
+        assert!(supersid.spectrum.iter().all(|spectrum| spectrum.N == get_N(48000f64)));
+        assert_peak(&supersid.spectrum[0], 5000.);
+        assert_peak(&supersid.spectrum[1], 9000.);
+        assert!(supersid.station_data[1].spectral_density() > 100. * supersid.station_data[2].spectral_density());
+        assert!(supersid.station_data[0].spectral_density() > 100. * supersid.station_data[2].spectral_density());
+    }
+}
@@ -0,0 +1,203 @@
+//! Fires readings on UTC boundaries, as the Python `SidTimer` does: the slots are the multiples of
+//! `log_interval` seconds since midnight UTC, so the readings of every site line up.
+
+/// Source of the UTC time, replaced by a simulated clock to exercise the scheduler.
+pub trait Clock {
+    fn now(&self) -> chrono::DateTime<chrono::Utc>;
+    fn sleep(&self, duration: std::time::Duration);
+}
+
+/// The system clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> chrono::DateTime<chrono::Utc> {
+        chrono::Utc::now()
+    }
+
+    fn sleep(&self, duration: std::time::Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// A slot reached by the scheduler.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slot {
+    /// Boundary the slot starts at.
+    pub scheduled: chrono::DateTime<chrono::Utc>,
+    /// Time the scheduler woke up at, with microsecond precision, to be logged with the reading.
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// Boundaries passed without a reading since the previous slot, because it overran.
+    pub missed: Vec<chrono::DateTime<chrono::Utc>>
+}
+
+pub struct Scheduler<C: Clock> {
+    clock: C,
+    interval: chrono::Duration,
+    next: chrono::DateTime<chrono::Utc>
+}
+
+impl<C: Clock> Scheduler<C> {
+    /// Longest single sleep, so a stop request is noticed quickly.
+    const MAX_SLEEP: std::time::Duration = std::time::Duration::from_millis(250);
+
+    /// Creates a scheduler whose first slot is the next boundary after the current time.
+    pub fn new(clock: C, interval_seconds: usize) -> Self {
+        let interval = chrono::Duration::seconds(interval_seconds as i64);
+        let next = boundary_after(clock.now(), interval);
+        Self { clock, interval, next }
+    }
+
+    /// Sleeps until the next slot and returns it, or `None` once `stop` is set.
+    ///
+    /// Each wake up targets an absolute time, so neither the processing time nor the sleep
+    /// inaccuracy accumulate as drift. When the previous slot overran one or more boundaries, the
+    /// reading is taken right away for the current slot and the skipped ones are listed in
+    /// [`Slot::missed`] rather than shifting the following readings.
+    pub fn wait(&mut self, stop: &std::sync::atomic::AtomicBool) -> Option<Slot> {
+        loop {
+            if stop.load(std::sync::atomic::Ordering::Relaxed) {
+                return None;
+            }
+
+            let now = self.clock.now();
+            if now < self.next {
+                let remaining = (self.next - now).to_std().unwrap_or_default();
+                self.clock.sleep(std::cmp::min(remaining, Self::MAX_SLEEP));
+                continue;
+            }
+
+            let scheduled = boundary_before(now, self.interval);
+            let mut missed = Vec::<chrono::DateTime<chrono::Utc>>::new();
+            while self.next < scheduled {
+                missed.push(self.next);
+                self.next = boundary_after(self.next, self.interval);
+            }
+            self.next = boundary_after(now, self.interval);
+            return Some(Slot { scheduled, timestamp: now, missed });
+        }
+    }
+}
+
+/// Returns the latest multiple of `interval` since midnight UTC at or before `time`.
+pub fn boundary_before(time: chrono::DateTime<chrono::Utc>, interval: chrono::Duration) -> chrono::DateTime<chrono::Utc> {
+    let midnight = time.date_naive().and_time(chrono::NaiveTime::MIN).and_utc();
+    let elapsed = (time - midnight).num_microseconds().unwrap_or_default();
+    let interval_micros = interval.num_microseconds().unwrap_or(i64::MAX);
+    midnight + chrono::Duration::microseconds(elapsed - elapsed % interval_micros)
+}
+
+/// Returns the boundary following `time`. Midnight is always a boundary, so the slots restart
+/// from it even when `interval` does not divide a day.
+pub fn boundary_after(time: chrono::DateTime<chrono::Utc>, interval: chrono::Duration) -> chrono::DateTime<chrono::Utc> {
+    let next_midnight = time.date_naive().and_time(chrono::NaiveTime::MIN).and_utc() + chrono::Duration::days(1);
+    std::cmp::min(boundary_before(time, interval) + interval, next_midnight)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Simulated time, shared with the test so it can spend time as a reading would. Each sleep
+    /// oversleeps by a few milliseconds, as a loaded system does.
+    #[derive(Clone)]
+    struct FakeClock {
+        now: std::rc::Rc<std::cell::Cell<chrono::DateTime<chrono::Utc>>>
+    }
+
+    impl FakeClock {
+        const OVERSLEEP: chrono::Duration = chrono::Duration::milliseconds(3);
+
+        fn at(time: &str) -> Self {
+            let now = chrono::NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S%.f").unwrap().and_utc();
+            Self { now: std::rc::Rc::new(std::cell::Cell::new(now)) }
+        }
+
+        fn spend(&self, duration: chrono::Duration) {
+            self.now.set(self.now.get() + duration);
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> chrono::DateTime<chrono::Utc> {
+            self.now.get()
+        }
+
+        fn sleep(&self, duration: std::time::Duration) {
+            self.spend(chrono::Duration::from_std(duration).unwrap() + Self::OVERSLEEP);
+        }
+    }
+
+    fn utc(time: &str) -> chrono::DateTime<chrono::Utc> {
+        chrono::NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S").unwrap().and_utc()
+    }
+
+    fn run() -> std::sync::atomic::AtomicBool {
+        std::sync::atomic::AtomicBool::new(false)
+    }
+
+    #[test]
+    fn slots_start_on_utc_boundaries() {
+        let clock = FakeClock::at("2026-10-18 12:00:03.7");
+        let mut scheduler = Scheduler::new(clock.clone(), 5);
+
+        for expected in ["2026-10-18 12:00:05", "2026-10-18 12:00:10", "2026-10-18 12:00:15"] {
+            let slot = scheduler.wait(&run()).unwrap();
+            assert_eq!(slot.scheduled, utc(expected));
+            assert!(slot.timestamp >= slot.scheduled && slot.timestamp - slot.scheduled <= FakeClock::OVERSLEEP);
+            assert!(slot.missed.is_empty());
+            clock.spend(chrono::Duration::milliseconds(1200));
+        }
+    }
+
+    #[test]
+    fn slots_restart_at_midnight() {
+        // 7 s does not divide a day, the last slot before midnight is shortened.
+        let clock = FakeClock::at("2026-10-18 23:59:50");
+        let mut scheduler = Scheduler::new(clock, 7);
+
+        let scheduled: Vec<chrono::DateTime<chrono::Utc>> = (0..3).map(|_| scheduler.wait(&run()).unwrap().scheduled).collect();
+        assert_eq!(scheduled, vec![utc("2026-10-18 23:59:54"), utc("2026-10-19 00:00:00"), utc("2026-10-19 00:00:07")]);
+    }
+
+    #[test]
+    fn overrun_slots_are_missed() {
+        let clock = FakeClock::at("2026-10-18 12:00:01");
+        let mut scheduler = Scheduler::new(clock.clone(), 5);
+        assert_eq!(scheduler.wait(&run()).unwrap().scheduled, utc("2026-10-18 12:00:05"));
+
+        clock.spend(chrono::Duration::seconds(12));
+        let slot = scheduler.wait(&run()).unwrap();
+        assert_eq!(slot.scheduled, utc("2026-10-18 12:00:15"));
+        assert_eq!(slot.timestamp, clock.now());
+        assert_eq!(slot.missed, vec![utc("2026-10-18 12:00:10")]);
+
+        let slot = scheduler.wait(&run()).unwrap();
+        assert_eq!(slot.scheduled, utc("2026-10-18 12:00:20"));
+        assert!(slot.missed.is_empty());
+    }
+
+    #[test]
+    fn slots_do_not_drift() {
+        let clock = FakeClock::at("2026-10-18 00:00:00.5");
+        let mut scheduler = Scheduler::new(clock.clone(), 5);
+        let first = scheduler.wait(&run()).unwrap().scheduled;
+
+        // Over a day of slots with readings of varying length.
+        let mut i = 1i32;
+        while i < 17280 {
+            clock.spend(chrono::Duration::milliseconds(1000 + 37 * (i % 50) as i64));
+            let slot = scheduler.wait(&run()).unwrap();
+            assert_eq!(slot.scheduled, first + chrono::Duration::seconds(5) * i);
+            assert!(slot.timestamp - slot.scheduled <= FakeClock::OVERSLEEP);
+            assert!(slot.missed.is_empty());
+            i += 1;
+        }
+    }
+
+    #[test]
+    fn stop_ends_the_wait() {
+        let mut scheduler = Scheduler::new(FakeClock::at("2026-10-18 12:00:01"), 5);
+        assert_eq!(scheduler.wait(&std::sync::atomic::AtomicBool::new(true)), None);
+    }
+}
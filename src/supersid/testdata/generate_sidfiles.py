@@ -0,0 +1,126 @@
+"""Writes the SID files the tests of sidfile.rs compare the Rust writer with.
+
+The files are written by the SidFile class of supersid/sidfile.py. When numpy
+is not installed, the few array operations sidfile.py uses are provided by the
+stand-in below, which computes them element by element in the same order as
+numpy does (cumsum included), so the output is the same to the last digit.
+
+Run from the repository root: python3 src/supersid/testdata/generate_sidfiles.py
+"""
+
+import math
+import os
+import random
+import sys
+import types
+
+HERE = os.path.dirname(os.path.abspath(__file__))
+sys.path.insert(0, os.path.join(HERE, "..", "..", "..", "supersid"))
+
+
+class Array(list):
+    """One or two dimensional float array supporting what sidfile.py uses."""
+
+    @property
+    def shape(self):
+        if self and isinstance(self[0], Array):
+            return (len(self), len(self[0]))
+        return (len(self),)
+
+    def __getitem__(self, index):
+        item = list.__getitem__(self, index)
+        return Array(item) if isinstance(index, slice) else item
+
+    def __setitem__(self, index, value):
+        if isinstance(index, slice) and not isinstance(value, list):
+            value = [value] * len(range(*index.indices(len(self))))
+        list.__setitem__(self, index, value)
+
+    def __sub__(self, other):
+        return Array(a - b for a, b in zip(self, other))
+
+    def __truediv__(self, divisor):
+        return Array(a / divisor for a in self)
+
+    def fill(self, value):
+        for i in range(len(self)):
+            if isinstance(self[i], Array):
+                self[i].fill(value)
+            else:
+                self[i] = value
+
+
+def zeros(shape):
+    if isinstance(shape, tuple):
+        return Array(Array([0.0] * shape[1]) for _ in range(shape[0]))
+    return Array([0.0] * shape)
+
+
+def cumsum(values, dtype=float):
+    total, result = 0.0, Array()
+    for value in values:
+        total += value
+        result.append(total)
+    return result
+
+
+try:
+    import numpy  # noqa: F401
+except ImportError:
+    sys.modules["numpy"] = types.SimpleNamespace(
+        zeros=zeros,
+        empty=lambda length, dtype=None: Array([None] * length),
+        hstack=lambda arrays: Array(v for array in arrays for v in array),
+        cumsum=cumsum,
+        array=lambda rows: Array(Array(row) for row in rows),
+        transpose=lambda rows: [Array(column) for column in zip(*rows)])
+
+from config import FILTERED, RAW  # noqa: E402
+from sidfile import SidFile  # noqa: E402
+
+
+def readings(generator, length):
+    """A smooth day curve with noise and spikes, written as "%.15f" would
+    write them so the raw file reads back to the same values."""
+    values = []
+    for i in range(length):
+        value = 1e-3 * (2 + math.sin(2 * math.pi * i / length))
+        value *= 1 + generator.gauss(0, 0.2)
+        if i % 17 == 5:
+            value *= 30
+        values.append(float("%.15f" % value))
+    return values
+
+
+def main():
+    sid = SidFile(sid_params={
+        "site_name": "TESTSITE",
+        "contact": "test@example.com",
+        "longitude": "-122.17",
+        "latitude": "37.43",
+        "utc_offset": "-08:00",
+        "time_zone": "US/Pacific",
+        "utc_starttime": "2026-10-18 00:00:00",
+        "log_interval": "900",
+        "monitor_id": "TEST",
+        "stations": "NWC,VTX3",
+        "frequencies": "19800,18200",
+    })
+    generator = random.Random(2026)
+    for station in range(len(sid.stations)):
+        for i, value in enumerate(readings(generator, len(sid.timestamp))):
+            sid.data[station][i] = value
+
+    sid.write_data_supersid(os.path.join(HERE, "supersid.raw.ext.csv"),
+                            RAW, extended=True)
+    sid.write_data_supersid(os.path.join(HERE, "supersid.filtered.ext.csv"),
+                            FILTERED, extended=True, bema_wing=6)
+    sid.write_data_supersid(os.path.join(HERE, "supersid.filtered.csv"),
+                            FILTERED, extended=False, bema_wing=6)
+    # Last since it adds the StationID of the station to sid_params.
+    sid.write_data_sid("VTX3", os.path.join(HERE, "sid.VTX3.filtered.csv"),
+                       FILTERED, extended=False, bema_wing=2)
+
+
+if __name__ == "__main__":
+    main()